@@ -4,6 +4,7 @@ import shlex
 import shutil
 import subprocess
 import sys
+import tarfile
 
 SUPPORTED_ARCHITECTURES = ["x86_64"]
 
@@ -48,6 +49,8 @@ def compile_kernel() -> bool:
     command = "cargo build"
     if "--release" in sys.argv:
         command += " --release"
+    if "test" in sys.argv:
+        command += " --features test-mode"
 
     os.chdir("kernel")
     print("Compiling kernel...")
@@ -81,9 +84,19 @@ def link_everything():
     command = f"ld -n --gc-sections -T {linker_script} -o {kernel_build_path} {assemblies} {rust_kernel_path}"
     subprocess.run(shlex.split(command))
 
+def make_initrd():
+    # Everything inside ./initrd shows up under / at boot
+    print("Making initrd...")
+    initrd_path = f"build/{ferricoxide_architecture}/iso_tree/boot/initrd.tar"
+    with tarfile.open(initrd_path, "w", format=tarfile.USTAR_FORMAT) as initrd:
+        if os.path.isdir("initrd"):
+            for entry in sorted(os.listdir("initrd")):
+                initrd.add(os.path.join("initrd", entry), arcname=entry)
+
 def make_iso():
     print("Making iso...")
     os.makedirs(f"build/{ferricoxide_architecture}/iso_tree/boot/grub")
+    make_initrd()
     shutil.copy(f"build/{ferricoxide_architecture}/{ferricoxide_architecture}-ferricoxide_os.bin", f"build/{ferricoxide_architecture}/iso_tree/boot/")
     shutil.copy(f"kernel/arch/{ferricoxide_architecture}/boot/grub/grub.cfg", f"build/{ferricoxide_architecture}/iso_tree/boot/grub")
     command = f"grub-mkrescue -o build/{ferricoxide_architecture}/{ferricoxide_architecture}-ferricoxide_os.iso build/{ferricoxide_architecture}/iso_tree"
@@ -94,6 +107,25 @@ def boot():
     command = f"qemu-system-{ferricoxide_architecture} -cdrom build/{ferricoxide_architecture}/{ferricoxide_architecture}-ferricoxide_os.iso -d cpu_reset -serial stdio -no-reboot -no-shutdown -s"
     subprocess.run(shlex.split(command))
 
+# ktest.rs exits through isa-debug-exit, which makes QEMU exit with `(code << 1) | 1`
+QEMU_EXIT_SUCCESS = (0x10 << 1) | 1
+
+def run_tests() -> bool:
+    print("Running tests...")
+    command = f"qemu-system-{ferricoxide_architecture} -cdrom build/{ferricoxide_architecture}/{ferricoxide_architecture}-ferricoxide_os.iso -device isa-debug-exit,iobase=0xf4,iosize=0x04 -serial stdio -display none -no-reboot"
+    try:
+        process = subprocess.run(shlex.split(command), timeout=120)
+    except subprocess.TimeoutExpired:
+        eprint("Tests timed out")
+        return False
+
+    if process.returncode != QEMU_EXIT_SUCCESS:
+        eprint(f"Tests failed, QEMU exited with {process.returncode}")
+        return False
+
+    print("Tests passed")
+    return True
+
 def format_kernel_code():
     command = "cargo fmt"
     os.chdir("kernel")
@@ -134,7 +166,10 @@ def main():
     link_everything()
     make_iso()
 
-    if "run" in sys.argv:
+    if "test" in sys.argv:
+        if not run_tests():
+            sys.exit(1)
+    elif "run" in sys.argv:
         boot()
 
 if __name__ == "__main__":
@@ -0,0 +1,166 @@
+use bitflags::bitflags;
+use core::arch::x86_64::{__cpuid, CpuidResult};
+
+const LEAF_VENDOR: u32 = 0;
+const LEAF_FEATURES: u32 = 1;
+const LEAF_EXTENDED_MAX: u32 = 0x8000_0000;
+const LEAF_HYPERVISOR: u32 = 0x4000_0000;
+/// The brand string is spread over these three leaves, 16 bytes each
+const LEAVES_BRAND: [u32; 3] = [0x8000_0002, 0x8000_0003, 0x8000_0004];
+
+bitflags! {
+    /// Leaf 1 feature bits, `edx` in the low half and `ecx` in the high half
+    pub struct CpuFeatures: u64 {
+        const FPU =     1 << 0;
+        const VME =     1 << 1;
+        const DE =      1 << 2;
+        const PSE =     1 << 3;
+        const TSC =     1 << 4;
+        const MSR =     1 << 5;
+        const PAE =     1 << 6;
+        const MCE =     1 << 7;
+        const CX8 =     1 << 8;
+        const APIC =    1 << 9;
+        const SEP =     1 << 11;
+        const MTRR =    1 << 12;
+        const PGE =     1 << 13;
+        const MCA =     1 << 14;
+        const CMOV =    1 << 15;
+        const PAT =     1 << 16;
+        const PSE36 =   1 << 17;
+        const CLFSH =   1 << 19;
+        const MMX =     1 << 23;
+        const FXSR =    1 << 24;
+        const SSE =     1 << 25;
+        const SSE2 =    1 << 26;
+        const HTT =     1 << 28;
+        const SSE3 =    1 << 32;
+        const PCLMUL =  1 << 33;
+        const SSSE3 =   1 << 41;
+        const FMA =     1 << 44;
+        const CX16 =    1 << 45;
+        const SSE41 =   1 << 51;
+        const SSE42 =   1 << 52;
+        const X2APIC =  1 << 53;
+        const MOVBE =   1 << 54;
+        const POPCNT =  1 << 55;
+        const AES =     1 << 57;
+        const XSAVE =   1 << 58;
+        const OSXSAVE = 1 << 59;
+        const AVX =     1 << 60;
+        const F16C =    1 << 61;
+        const RDRAND =  1 << 62;
+        const HYPERVISOR = 1 << 63;
+    }
+}
+
+/// Names `CpuFeatures::names` prints, in bit order
+const FEATURE_NAMES: &[(CpuFeatures, &str)] = &[
+    (CpuFeatures::FPU, "fpu"),
+    (CpuFeatures::VME, "vme"),
+    (CpuFeatures::DE, "de"),
+    (CpuFeatures::PSE, "pse"),
+    (CpuFeatures::TSC, "tsc"),
+    (CpuFeatures::MSR, "msr"),
+    (CpuFeatures::PAE, "pae"),
+    (CpuFeatures::MCE, "mce"),
+    (CpuFeatures::CX8, "cx8"),
+    (CpuFeatures::APIC, "apic"),
+    (CpuFeatures::SEP, "sep"),
+    (CpuFeatures::MTRR, "mtrr"),
+    (CpuFeatures::PGE, "pge"),
+    (CpuFeatures::MCA, "mca"),
+    (CpuFeatures::CMOV, "cmov"),
+    (CpuFeatures::PAT, "pat"),
+    (CpuFeatures::PSE36, "pse36"),
+    (CpuFeatures::CLFSH, "clflush"),
+    (CpuFeatures::MMX, "mmx"),
+    (CpuFeatures::FXSR, "fxsr"),
+    (CpuFeatures::SSE, "sse"),
+    (CpuFeatures::SSE2, "sse2"),
+    (CpuFeatures::HTT, "ht"),
+    (CpuFeatures::SSE3, "sse3"),
+    (CpuFeatures::PCLMUL, "pclmulqdq"),
+    (CpuFeatures::SSSE3, "ssse3"),
+    (CpuFeatures::FMA, "fma"),
+    (CpuFeatures::CX16, "cx16"),
+    (CpuFeatures::SSE41, "sse4_1"),
+    (CpuFeatures::SSE42, "sse4_2"),
+    (CpuFeatures::X2APIC, "x2apic"),
+    (CpuFeatures::MOVBE, "movbe"),
+    (CpuFeatures::POPCNT, "popcnt"),
+    (CpuFeatures::AES, "aes"),
+    (CpuFeatures::XSAVE, "xsave"),
+    (CpuFeatures::OSXSAVE, "osxsave"),
+    (CpuFeatures::AVX, "avx"),
+    (CpuFeatures::F16C, "f16c"),
+    (CpuFeatures::RDRAND, "rdrand"),
+    (CpuFeatures::HYPERVISOR, "hypervisor"),
+];
+
+impl CpuFeatures {
+    /// Names of the set features, `/proc/cpuinfo` style
+    pub fn names(self) -> impl Iterator<Item = &'static str> {
+        FEATURE_NAMES
+            .iter()
+            .filter(move |(feature, _)| self.contains(*feature))
+            .map(|(_, name)| *name)
+    }
+}
+
+fn cpuid(leaf: u32) -> CpuidResult {
+    // `__cpuid` stopped being unsafe in newer toolchains
+    #[allow(unused_unsafe)]
+    unsafe {
+        __cpuid(leaf)
+    }
+}
+
+/// Vendor string, e.g. `GenuineIntel` or `AuthenticAMD`
+pub fn vendor() -> String {
+    let leaf = cpuid(LEAF_VENDOR);
+    let bytes = [leaf.ebx, leaf.edx, leaf.ecx]
+        .iter()
+        .flat_map(|register| register.to_le_bytes())
+        .collect::<Vec<_>>();
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Brand string, e.g. `QEMU Virtual CPU version 2.5+`. `None` if the CPU doesn't have one.
+pub fn brand() -> Option<String> {
+    if cpuid(LEAF_EXTENDED_MAX).eax < LEAVES_BRAND[2] {
+        return None;
+    }
+
+    let bytes = LEAVES_BRAND
+        .iter()
+        .map(|leaf| cpuid(*leaf))
+        .flat_map(|leaf| [leaf.eax, leaf.ebx, leaf.ecx, leaf.edx])
+        .flat_map(|register| register.to_le_bytes())
+        .take_while(|byte| *byte != 0)
+        .collect::<Vec<_>>();
+
+    Some(String::from_utf8_lossy(&bytes).trim().into())
+}
+
+pub fn features() -> CpuFeatures {
+    let leaf = cpuid(LEAF_FEATURES);
+
+    CpuFeatures::from_bits_truncate(leaf.edx as u64 | (leaf.ecx as u64) << 32)
+}
+
+/// Hypervisor signature, e.g. `TCGTCGTCGTCG` for QEMU. `None` on bare metal.
+pub fn hypervisor_signature() -> Option<String> {
+    if !features().contains(CpuFeatures::HYPERVISOR) {
+        return None;
+    }
+
+    let leaf = cpuid(LEAF_HYPERVISOR);
+    let bytes = [leaf.ebx, leaf.ecx, leaf.edx]
+        .iter()
+        .flat_map(|register| register.to_le_bytes())
+        .collect::<Vec<_>>();
+
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
@@ -0,0 +1,116 @@
+use crate::kutils::errors::ErrorCode;
+use core::arch::asm;
+use x86_64::registers::debug::{
+    BreakpointCondition, BreakpointSize, DebugAddressRegister, DebugAddressRegisterNumber, Dr0,
+    Dr1, Dr2, Dr3, Dr6, Dr6Flags, Dr7, Dr7Flags, Dr7Value,
+};
+
+/// Number of hardware breakpoints, one per address register DR0-DR3
+pub const BREAKPOINT_SLOTS: u8 = 4;
+
+/// Access that triggers a hardware breakpoint
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BreakpointKind {
+    /// Executing the instruction at the address, fires before it runs
+    Exec,
+    /// Writing to the address, fires after the write
+    Write,
+    /// Reading or writing the address, fires after the access
+    ReadWrite,
+}
+
+impl BreakpointKind {
+    fn condition(self) -> BreakpointCondition {
+        match self {
+            BreakpointKind::Exec => BreakpointCondition::InstructionExecution,
+            BreakpointKind::Write => BreakpointCondition::DataWrites,
+            BreakpointKind::ReadWrite => BreakpointCondition::DataReadsWrites,
+        }
+    }
+}
+
+/// Arms hardware breakpoint `slot` (0-3) on `len` bytes (1, 2, 4 or 8) at `addr`.
+/// Execution breakpoints must have a length of 1 and data breakpoints an address aligned to
+/// their length, anything else fails with `EINVAL`.
+#[allow(unused)]
+pub fn set_hw_breakpoint(
+    slot: u8,
+    addr: u64,
+    kind: BreakpointKind,
+    len: usize,
+) -> Result<(), ErrorCode> {
+    let n = DebugAddressRegisterNumber::new(slot).ok_or(ErrorCode::EINVAL)?;
+    let dr7 = arm_dr7(Dr7::read_raw(), slot, addr, kind, len)?;
+
+    write_address(n, addr);
+    Dr7::write_raw(dr7);
+
+    Ok(())
+}
+
+/// Returns `dr7` with slot `slot` enabled for `kind` accesses to `len` bytes at `addr`,
+/// leaving the other slots alone. Fails with `EINVAL` on the same arguments as
+/// [`set_hw_breakpoint`].
+pub fn arm_dr7(
+    dr7: u64,
+    slot: u8,
+    addr: u64,
+    kind: BreakpointKind,
+    len: usize,
+) -> Result<u64, ErrorCode> {
+    let n = DebugAddressRegisterNumber::new(slot).ok_or(ErrorCode::EINVAL)?;
+    let size = BreakpointSize::new(len).ok_or(ErrorCode::EINVAL)?;
+    if (kind == BreakpointKind::Exec && len != 1) || addr % len as u64 != 0 {
+        return Err(ErrorCode::EINVAL);
+    }
+
+    let mut dr7 = Dr7Value::from_bits_truncate(dr7);
+    dr7.set_condition(n, kind.condition());
+    dr7.set_size(n, size);
+    dr7.insert_flags(Dr7Flags::local_breakpoint_enable(n));
+
+    Ok(dr7.bits())
+}
+
+/// Disarms hardware breakpoint `slot`
+#[allow(unused)]
+pub fn clear_hw_breakpoint(slot: u8) -> Result<(), ErrorCode> {
+    let n = DebugAddressRegisterNumber::new(slot).ok_or(ErrorCode::EINVAL)?;
+
+    let mut dr7 = Dr7::read();
+    dr7.remove_flags(Dr7Flags::local_breakpoint_enable(n));
+    Dr7::write(dr7);
+
+    Ok(())
+}
+
+/// Returns the first hardware breakpoint DR6 says fired, and clears DR6.
+/// The CPU never clears it itself, so a stale hit would otherwise show up on the next #DB.
+pub fn take_triggered_breakpoint() -> Option<u8> {
+    let status = Dr6::read();
+    unsafe {
+        asm!("mov dr6, {}", in(reg) 0u64, options(nomem, nostack, preserves_flags));
+    }
+
+    (0..BREAKPOINT_SLOTS).find(|&slot| {
+        let n = DebugAddressRegisterNumber::new(slot).unwrap();
+        status.contains(Dr6Flags::trap(n))
+    })
+}
+
+/// Whether `slot` is an execution breakpoint, which has to be resumed past with RFLAGS.RF
+pub fn is_exec_breakpoint(slot: u8) -> bool {
+    DebugAddressRegisterNumber::new(slot)
+        .map(|n| Dr7::read().condition(n) == BreakpointCondition::InstructionExecution)
+        .unwrap_or(false)
+}
+
+fn write_address(n: DebugAddressRegisterNumber, addr: u64) {
+    match n {
+        DebugAddressRegisterNumber::Dr0 => Dr0::write(addr),
+        DebugAddressRegisterNumber::Dr1 => Dr1::write(addr),
+        DebugAddressRegisterNumber::Dr2 => Dr2::write(addr),
+        DebugAddressRegisterNumber::Dr3 => Dr3::write(addr),
+    }
+}
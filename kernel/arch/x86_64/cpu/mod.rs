@@ -1,3 +1,6 @@
+pub mod cpuid;
+pub mod debug;
+pub mod percpu;
 pub mod registers;
 
 use crate::arch::cpu::registers::Registers;
@@ -18,22 +21,43 @@ impl Cpu {
         x86_64::instructions::interrupts::disable();
     }
 
-    pub fn without_interrupts<F>(f: F)
+    /// Runs `f` with interrupts disabled and returns its result.
+    ///
+    /// Interrupts are only re-enabled afterwards if they were enabled before.
+    pub fn without_interrupts<F, R>(f: F) -> R
     where
-        F: Fn(),
+        F: FnOnce() -> R,
     {
-        x86_64::instructions::interrupts::without_interrupts(f);
+        x86_64::instructions::interrupts::without_interrupts(f)
+    }
+
+    /// Emits `pause`, telling the CPU we're in a busy-wait loop.
+    ///
+    /// Used in the PIT's one-shot countdown poll.
+    #[inline]
+    pub fn spin_loop_hint() {
+        core::hint::spin_loop();
     }
 
     pub fn dump_registers() {
         Registers::read_regs().dump_regs();
     }
 
+    /// Stops the CPU for good. Interrupts are disabled first so nothing wakes it up again.
     pub fn halt() -> ! {
+        Self::disable_interrupts();
         loop {
             unsafe {
                 asm!("hlt");
             }
         }
     }
+
+    /// Sleeps until the next interrupt, forever. Unlike `halt`, interrupt handlers keep running.
+    #[allow(unused)]
+    pub fn idle_loop() -> ! {
+        loop {
+            Self::enable_interrupts_and_halt();
+        }
+    }
 }
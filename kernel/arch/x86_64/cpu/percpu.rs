@@ -0,0 +1,96 @@
+use crate::arch::cpu::Cpu;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use core::any::{Any, TypeId};
+use core::arch::asm;
+use core::ptr;
+use spin::Mutex;
+use x86_64::registers::model_specific::GsBase;
+use x86_64::VirtAddr;
+
+/// Data each core keeps its own copy of. IA32_GS_BASE points at it.
+#[repr(C)]
+pub struct PerCpu {
+    /// Points back at the block so it can be located with a single `gs:` load.
+    /// Must stay the first field.
+    self_ptr: *const PerCpu,
+    cpu_id: usize,
+    /// Values stored with [`set`], one per type. Only this core locks it, the lock is
+    /// there so `set` doesn't need a mutable reference to the block.
+    locals: Mutex<BTreeMap<TypeId, Box<dyn Any>>>,
+}
+
+impl PerCpu {
+    #[allow(unused)]
+    pub fn cpu_id(&self) -> usize {
+        self.cpu_id
+    }
+}
+
+/// Allocates the per-core block for the calling core and points GS base at it.
+///
+/// Needs the heap. The block is never freed.
+pub fn init(cpu_id: usize) {
+    let per_cpu = Box::leak(Box::new(PerCpu {
+        self_ptr: ptr::null(),
+        cpu_id,
+        locals: Mutex::new(BTreeMap::new()),
+    }));
+    per_cpu.self_ptr = per_cpu as *const PerCpu;
+
+    GsBase::write(VirtAddr::new(per_cpu.self_ptr as u64));
+}
+
+/// Returns the calling core's per-core block.
+///
+/// Panics if `init` hasn't run on this core yet.
+#[allow(unused)]
+pub fn current() -> &'static PerCpu {
+    // GS base is 0 until `init` ran on this core. A global flag would only say that some
+    // core ran it.
+    assert_ne!(
+        GsBase::read().as_u64(),
+        0,
+        "per-CPU data used before percpu::init on this core"
+    );
+
+    let per_cpu: *const PerCpu;
+    unsafe {
+        asm!(
+            "mov {}, gs:[0]",
+            out(reg) per_cpu,
+            options(nostack, readonly, preserves_flags)
+        );
+
+        &*per_cpu
+    }
+}
+
+/// Stores `value` in the calling core's block, where [`get`] finds it by its type.
+/// Hands `value` back if this core already has a `T`, references to the old one may be around.
+#[allow(unused)]
+pub fn set<T: Any>(value: T) -> Result<(), T> {
+    // an interrupt handler calling `get` on this core would spin on the lock forever
+    Cpu::without_interrupts(|| {
+        let mut locals = current().locals.lock();
+        if locals.contains_key(&TypeId::of::<T>()) {
+            return Err(value);
+        }
+
+        locals.insert(TypeId::of::<T>(), Box::new(value));
+        Ok(())
+    })
+}
+
+/// Returns the calling core's `T`, `None` if [`set`] wasn't called with one on this core
+#[allow(unused)]
+pub fn get<T: Any>() -> Option<&'static T> {
+    Cpu::without_interrupts(|| {
+        let locals = current().locals.lock();
+        let value = locals.get(&TypeId::of::<T>())?.downcast_ref::<T>()?;
+
+        // SAFETY: values are boxed and never replaced or removed, so they live as long as
+        // the block, which is never freed
+        Some(unsafe { &*(value as *const T) })
+    })
+}
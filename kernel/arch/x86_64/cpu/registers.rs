@@ -1,117 +1,143 @@
 use core::arch::asm;
+use core::fmt::{Display, Formatter};
 use x86_64::registers::read_rip;
 
-#[derive(Default)]
+/// General purpose registers and the instruction pointer.
+///
+/// Field order matters, `capture` and `restore` address fields by offset.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
 pub struct Registers {
-    rip: u64,
-    rax: u64,
-    rbx: u64,
-    rcx: u64,
-    rdx: u64,
-    rsi: u64,
-    rdi: u64,
-    rbp: u64,
-    rsp: u64,
-    r8: u64,
-    r9: u64,
-    r10: u64,
-    r11: u64,
-    r12: u64,
-    r13: u64,
-    r14: u64,
-    r15: u64,
+    pub rip: u64,
+    pub rax: u64,
+    pub rbx: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub rbp: u64,
+    pub rsp: u64,
+    pub r8: u64,
+    pub r9: u64,
+    pub r10: u64,
+    pub r11: u64,
+    pub r12: u64,
+    pub r13: u64,
+    pub r14: u64,
+    pub r15: u64,
 }
 
 impl Registers {
     pub fn read_regs() -> Self {
-        let rip = read_rip().as_u64();
-        let rax;
-        let rbx;
-        let rcx;
-        let rdx;
-        let rsi;
-        let rdi;
-        let rbp;
-        let rsp;
-        let r8;
-        let r9;
-        let r10;
-        let r11;
-        let r12;
-        let r13;
-        let r14;
-        let r15;
+        Self::capture()
+    }
+
+    /// Snapshots the registers in a single asm block, so the compiler can't reuse any of them
+    /// halfway through. `rdi` holds the address of the snapshot, so its own value is lost.
+    #[inline(always)]
+    pub fn capture() -> Self {
+        let mut regs = Self {
+            rip: read_rip().as_u64(),
+            ..Default::default()
+        };
 
         unsafe {
-            asm!("mov {}, rax", out(reg) rax, options(nostack));
-            asm!("mov {}, rbx", out(reg) rbx, options(nostack));
-            asm!("mov {}, rcx", out(reg) rcx, options(nostack));
-            asm!("mov {}, rdx", out(reg) rdx, options(nostack));
-            asm!("mov {}, rsi", out(reg) rsi, options(nostack));
-            asm!("mov {}, rdi", out(reg) rdi, options(nostack));
-            asm!("mov {}, rbp", out(reg) rbp, options(nostack));
-            asm!("mov {}, rsp", out(reg) rsp, options(nostack));
-            asm!("mov {}, r8", out(reg) r8, options(nostack));
-            asm!("mov {}, r9", out(reg) r9, options(nostack));
-            asm!("mov {}, r10", out(reg) r10, options(nostack));
-            asm!("mov {}, r11", out(reg) r11, options(nostack));
-            asm!("mov {}, r12", out(reg) r12, options(nostack));
-            asm!("mov {}, r13", out(reg) r13, options(nostack));
-            asm!("mov {}, r14", out(reg) r14, options(nostack));
-            asm!("mov {}, r15", out(reg) r15, options(nostack));
+            asm!(
+                "mov [rdi + 0x08], rax",
+                "mov [rdi + 0x10], rbx",
+                "mov [rdi + 0x18], rcx",
+                "mov [rdi + 0x20], rdx",
+                "mov [rdi + 0x28], rsi",
+                "mov [rdi + 0x30], rdi",
+                "mov [rdi + 0x38], rbp",
+                "mov [rdi + 0x40], rsp",
+                "mov [rdi + 0x48], r8",
+                "mov [rdi + 0x50], r9",
+                "mov [rdi + 0x58], r10",
+                "mov [rdi + 0x60], r11",
+                "mov [rdi + 0x68], r12",
+                "mov [rdi + 0x70], r13",
+                "mov [rdi + 0x78], r14",
+                "mov [rdi + 0x80], r15",
+                in("rdi") &mut regs as *mut Self,
+                options(nostack, preserves_flags),
+            );
         }
 
-        Self {
-            rip,
-            rax,
-            rbx,
-            rcx,
-            rdx,
-            rsi,
-            rdi,
-            rbp,
-            rsp,
-            r8,
-            r9,
-            r10,
-            r11,
-            r12,
-            r13,
-            r14,
-            r15,
-        }
+        regs
     }
 
-    pub fn dump_regs(&self) {
-        print_raw!("Registers");
-        print_raw!("Instruction pointer=0x{:x}\n", self.rip);
-        print_raw!(
-            "rax=0x{:x}, rbx=0x{:x}, rcx=0x{:x}, rdx=0x{:x},\n",
-            self.rax,
-            self.rbx,
-            self.rcx,
-            self.rdx
-        );
-        print_raw!(
-            "rsi=0x{:x}, rdi=0x{:x}, rbp=0x{:x}, rsp=0x{:x},\n",
-            self.rsi,
-            self.rdi,
-            self.rbp,
-            self.rsp
-        );
-        print_raw!(
-            "r8=0x{:x}, r9=0x{:x}, r10=0x{:x}, r11=0x{:x},\n",
-            self.r8,
-            self.r9,
-            self.r10,
-            self.r11
-        );
-        print_raw!(
-            "r12=0x{:x}, r13=0x{:x}, r14=0x{:x}, r15=0x{:x}\n",
-            self.r12,
-            self.r13,
-            self.r14,
-            self.r15
+    /// Loads the general purpose registers back from the snapshot.
+    ///
+    /// `rip` and `rsp` are left alone, switching those means switching to another stack and
+    /// jumping, which has to happen on an interrupt return path. `rbx` and `rbp` are left
+    /// alone as well, the compiler reserves them and can't be told they changed.
+    ///
+    /// # SAFETY
+    /// The loaded values only last until the compiler reuses the registers, so this is only
+    /// meaningful right before control passes to code that expects them, such as returning
+    /// from an interrupt into the captured context.
+    #[allow(unused)]
+    pub unsafe fn restore(&self) {
+        asm!(
+            "mov rax, [rdi + 0x08]",
+            "mov rcx, [rdi + 0x18]",
+            "mov rdx, [rdi + 0x20]",
+            "mov rsi, [rdi + 0x28]",
+            "mov r8, [rdi + 0x48]",
+            "mov r9, [rdi + 0x50]",
+            "mov r10, [rdi + 0x58]",
+            "mov r11, [rdi + 0x60]",
+            "mov r12, [rdi + 0x68]",
+            "mov r13, [rdi + 0x70]",
+            "mov r14, [rdi + 0x78]",
+            "mov r15, [rdi + 0x80]",
+            "mov rdi, [rdi + 0x30]",
+            inout("rdi") self as *const Self => _,
+            out("rax") _,
+            out("rcx") _,
+            out("rdx") _,
+            out("rsi") _,
+            out("r8") _,
+            out("r9") _,
+            out("r10") _,
+            out("r11") _,
+            out("r12") _,
+            out("r13") _,
+            out("r14") _,
+            out("r15") _,
+            options(nostack, preserves_flags, readonly),
         );
     }
+
+    pub fn dump_regs(&self) {
+        print_raw!("{}", self);
+    }
+}
+
+impl Display for Registers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "Registers")?;
+        writeln!(f, "Instruction pointer=0x{:x}", self.rip)?;
+        writeln!(
+            f,
+            "rax=0x{:x}, rbx=0x{:x}, rcx=0x{:x}, rdx=0x{:x},",
+            self.rax, self.rbx, self.rcx, self.rdx
+        )?;
+        writeln!(
+            f,
+            "rsi=0x{:x}, rdi=0x{:x}, rbp=0x{:x}, rsp=0x{:x},",
+            self.rsi, self.rdi, self.rbp, self.rsp
+        )?;
+        writeln!(
+            f,
+            "r8=0x{:x}, r9=0x{:x}, r10=0x{:x}, r11=0x{:x},",
+            self.r8, self.r9, self.r10, self.r11
+        )?;
+        writeln!(
+            f,
+            "r12=0x{:x}, r13=0x{:x}, r14=0x{:x}, r15=0x{:x}",
+            self.r12, self.r13, self.r14, self.r15
+        )
+    }
 }
@@ -0,0 +1,90 @@
+use crate::arch::cpu::Cpu;
+use spin::Mutex;
+use x86_64::structures::idt::InterruptStackFrame;
+
+/// Exceptions whose handling can be changed with [`set_exception_policy`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ExceptionKind {
+    DivideError,
+    GeneralProtection,
+}
+
+const EXCEPTION_KIND_COUNT: usize = 2;
+/// Times the same exception may hit the same instruction in a row before it's a panic
+const MAX_REPEATED_FAULTS: usize = 16;
+
+/// What to do once an exception has been logged
+#[allow(unused)]
+#[derive(Debug, Clone, Copy)]
+pub enum Policy {
+    /// Stop the machine
+    Halt,
+    /// Return to the faulting instruction
+    Log,
+    /// Call the function, then return to wherever the stack frame points. The function can
+    /// move the instruction pointer past the faulting instruction to skip it.
+    Callback(fn(ExceptionKind, &mut InterruptStackFrame)),
+}
+
+static POLICIES: Mutex<[Policy; EXCEPTION_KIND_COUNT]> =
+    Mutex::new([Policy::Halt; EXCEPTION_KIND_COUNT]);
+static REPEATED_FAULTS: Mutex<RepeatedFaults> = Mutex::new(RepeatedFaults::new());
+
+/// Counts how often the same exception hit the same instruction in a row
+pub struct RepeatedFaults {
+    last: Option<(ExceptionKind, u64)>,
+    count: usize,
+}
+
+impl RepeatedFaults {
+    pub const fn new() -> Self {
+        Self {
+            last: None,
+            count: 0,
+        }
+    }
+
+    /// Records a fault at `instruction_pointer` and returns how many times in a row it hit
+    /// that instruction
+    pub fn record(&mut self, kind: ExceptionKind, instruction_pointer: u64) -> usize {
+        if self.last == Some((kind, instruction_pointer)) {
+            self.count += 1;
+        } else {
+            self.last = Some((kind, instruction_pointer));
+            self.count = 1;
+        }
+
+        self.count
+    }
+}
+
+/// Changes how `kind` is handled. Every exception defaults to [`Policy::Halt`].
+///
+/// Faults return to the instruction that caused them, so unless the callback skips it or
+/// removes the cause, `Log` and `Callback` fault again right away. After
+/// `MAX_REPEATED_FAULTS` faults in a row at the same instruction the kernel panics.
+#[allow(unused)]
+pub fn set_exception_policy(kind: ExceptionKind, policy: Policy) {
+    POLICIES.lock()[kind as usize] = policy;
+}
+
+/// Applies the policy for `kind`. Called by the exception handlers after logging.
+pub fn handle(kind: ExceptionKind, stack_frame: &mut InterruptStackFrame) {
+    let policy = POLICIES.lock()[kind as usize];
+    if let Policy::Halt = policy {
+        Cpu::halt();
+    }
+
+    let instruction_pointer = stack_frame.instruction_pointer.as_u64();
+    let repeats = REPEATED_FAULTS.lock().record(kind, instruction_pointer);
+    if repeats >= MAX_REPEATED_FAULTS {
+        panic!(
+            "{:?} at {:#x} kept faulting {} times in a row",
+            kind, instruction_pointer, repeats
+        );
+    }
+
+    if let Policy::Callback(callback) = policy {
+        callback(kind, stack_frame);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::arch::cpu::cpuid::{self, CpuFeatures};
+use crate::arch::x86_64::mm::paging::VirtualAddress;
+use crate::arch::x86_64::mm::MemoryController;
+use spin::Once;
+use x86_64::registers::model_specific::Msr;
+
+const IA32_APIC_BASE: u32 = 0x1b;
+/// Bits 12-51 of `IA32_APIC_BASE` hold the physical base of the registers
+const APIC_BASE_ADDRESS_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+/// The register page is 4KiB, registers are 32 bits wide and 16 byte aligned
+const REGISTERS_SIZE: usize = 0x1000;
+
+pub const REG_ID: usize = 0x20;
+pub const REG_VERSION: usize = 0x30;
+pub const REG_EOI: usize = 0xb0;
+pub const REG_SPURIOUS: usize = 0xf0;
+pub const REG_ICR_LOW: usize = 0x300;
+pub const REG_ICR_HIGH: usize = 0x310;
+pub const REG_LVT_TIMER: usize = 0x320;
+pub const REG_TIMER_INITIAL_COUNT: usize = 0x380;
+pub const REG_TIMER_CURRENT_COUNT: usize = 0x390;
+pub const REG_TIMER_DIVIDE: usize = 0x3e0;
+
+/// Spurious-interrupt vector register: APIC software enable
+const SPURIOUS_APIC_ENABLE: u32 = 1 << 8;
+
+/// Vector the local APIC raises spurious interrupts on
+pub const SPURIOUS_VECTOR: u8 = 0xff;
+
+pub static LAPIC: Once<Lapic> = Once::new();
+
+/// The local APIC of the current CPU, accessed through its memory mapped registers
+pub struct Lapic {
+    base: VirtualAddress,
+}
+
+#[allow(unused)]
+impl Lapic {
+    pub fn id(&self) -> u8 {
+        (self.read(REG_ID) >> 24) as u8
+    }
+
+    pub fn version(&self) -> u32 {
+        self.read(REG_VERSION)
+    }
+
+    /// Signals the end of the interrupt being serviced
+    pub fn eoi(&self) {
+        self.write(REG_EOI, 0);
+    }
+
+    /// Software-enables the local APIC, raising spurious interrupts on `spurious_vector`
+    pub fn enable(&self, spurious_vector: u8) {
+        self.set_spurious(spurious_register_value(spurious_vector));
+    }
+
+    pub fn spurious(&self) -> u32 {
+        self.read(REG_SPURIOUS)
+    }
+
+    pub fn set_spurious(&self, value: u32) {
+        self.write(REG_SPURIOUS, value);
+    }
+
+    /// Sends an IPI. Writing the low half is what sends it, so the destination goes first.
+    pub fn send_ipi(&self, destination: u8, command: u32) {
+        self.write(REG_ICR_HIGH, (destination as u32) << 24);
+        self.write(REG_ICR_LOW, command);
+    }
+
+    pub fn lvt_timer(&self) -> u32 {
+        self.read(REG_LVT_TIMER)
+    }
+
+    pub fn set_lvt_timer(&self, value: u32) {
+        self.write(REG_LVT_TIMER, value);
+    }
+
+    /// The timer counts down at the bus frequency divided by `divide`, see the SDM for the
+    /// encoding
+    pub fn set_timer_divide(&self, divide: u32) {
+        self.write(REG_TIMER_DIVIDE, divide);
+    }
+
+    /// Starts the countdown, writing 0 stops the timer
+    pub fn set_timer_initial_count(&self, count: u32) {
+        self.write(REG_TIMER_INITIAL_COUNT, count);
+    }
+
+    pub fn timer_current_count(&self) -> u32 {
+        self.read(REG_TIMER_CURRENT_COUNT)
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        // SAFETY: `base` maps the whole register page uncacheable, see `init`
+        unsafe { core::ptr::read_volatile((self.base + offset) as *const u32) }
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        // SAFETY: see `read`
+        unsafe { core::ptr::write_volatile((self.base + offset) as *mut u32, value) }
+    }
+}
+
+fn spurious_register_value(spurious_vector: u8) -> u32 {
+    SPURIOUS_APIC_ENABLE | spurious_vector as u32
+}
+
+/// Maps and enables the local APIC. Does nothing on CPUs without a local APIC.
+pub fn init(memory_controller: &mut MemoryController) {
+    if !cpuid::features().contains(CpuFeatures::APIC) {
+        warn!("CPU has no local APIC");
+        return;
+    }
+
+    let phys = unsafe { Msr::new(IA32_APIC_BASE).read() } & APIC_BASE_ADDRESS_MASK;
+    let base = match memory_controller.map_mmio(phys as usize, REGISTERS_SIZE) {
+        Ok(base) => base,
+        Err(err) => {
+            error!("Failed to map local APIC at {:#x}: {:?}", phys, err);
+            return;
+        }
+    };
+
+    let lapic = LAPIC.call_once(|| Lapic { base });
+    lapic.enable(SPURIOUS_VECTOR);
+    info!(
+        "Local APIC at {:#x}, id: {}, version: {:#x}",
+        phys,
+        lapic.id(),
+        lapic.version() & 0xff
+    );
+}
@@ -1,20 +1,29 @@
+pub mod exceptions;
 mod gdt;
+pub mod lapic;
+pub mod pit8254;
+pub mod pit_monitor;
+pub mod single_step;
 
 use gdt::Gdt;
 use lazy_static::lazy_static;
 
 use crate::arch::cpu::Cpu;
+use crate::arch::interrupts::exceptions::ExceptionKind;
 use crate::arch::mm::paging::entry::EntryFlags;
 use crate::arch::x86_64::mm::MemoryController;
+use crate::logging::serial::PanicSerial;
+use core::fmt::Write;
 use pic8259::ChainedPics;
 use spin::{Mutex, Once};
-use x86_64::instructions::hlt;
 use x86_64::instructions::port::Port;
 use x86_64::instructions::segmentation::Segment;
 use x86_64::instructions::tables::load_tss;
 use x86_64::registers::segmentation::CS;
 use x86_64::structures::gdt::SegmentSelector;
-use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode};
+use x86_64::structures::idt::{
+    EntryOptions, InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode,
+};
 use x86_64::structures::tss::TaskStateSegment;
 use x86_64::VirtAddr;
 
@@ -30,24 +39,72 @@ pub static PICS: Mutex<ChainedPics> =
 lazy_static! {
     static ref IDT: InterruptDescriptorTable = {
         let mut idt = InterruptDescriptorTable::new();
-        idt.breakpoint.set_handler_fn(breakpoint_handler);
+        idt.breakpoint
+            .set_handler_fn(breakpoint_handler)
+            .set_gate_type(GateType::Trap);
 
-        idt.page_fault.set_handler_fn(page_fault_handler);
+        idt.debug
+            .set_handler_fn(debug_handler)
+            .set_gate_type(GateType::Interrupt);
+
+        idt.divide_error
+            .set_handler_fn(divide_error_handler)
+            .set_gate_type(GateType::Interrupt);
+
+        idt.general_protection_fault
+            .set_handler_fn(general_protection_fault_handler)
+            .set_gate_type(GateType::Interrupt);
+
+        idt.page_fault
+            .set_handler_fn(page_fault_handler)
+            .set_gate_type(GateType::Interrupt);
 
         unsafe {
             idt.double_fault
                 .set_handler_fn(double_fault_handler)
+                .set_gate_type(GateType::Interrupt)
                 .set_stack_index(DOUBLE_FAULT_IST_INDEX as u16);
         }
 
-        idt[InterruptIndex::Timer.as_usize()].set_handler_fn(timer_interrupt_handler);
+        idt[InterruptIndex::Timer.as_usize()]
+            .set_handler_fn(timer_interrupt_handler)
+            .set_gate_type(GateType::Interrupt);
+
+        idt[InterruptIndex::Keyboard.as_usize()]
+            .set_handler_fn(keyboard_interrupt_handler)
+            .set_gate_type(GateType::Interrupt);
+
+        idt[pit_monitor::PIT_MONITOR_VECTOR as usize]
+            .set_handler_fn(pit_monitor_interrupt_handler)
+            .set_gate_type(GateType::Interrupt);
 
-        idt[InterruptIndex::Keyboard.as_usize()].set_handler_fn(keyboard_interrupt_handler);
+        idt[lapic::SPURIOUS_VECTOR as usize]
+            .set_handler_fn(spurious_interrupt_handler)
+            .set_gate_type(GateType::Interrupt);
 
         idt
     };
 }
 
+/// Decides whether interrupts stay enabled while a handler runs
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum GateType {
+    /// Interrupts are disabled on entry and restored on `iretq`
+    Interrupt,
+    /// Interrupts are left as they were, so the handler itself can get interrupted
+    Trap,
+}
+
+trait GateTypeExt {
+    fn set_gate_type(&mut self, gate_type: GateType) -> &mut Self;
+}
+
+impl GateTypeExt for EntryOptions {
+    fn set_gate_type(&mut self, gate_type: GateType) -> &mut Self {
+        self.disable_interrupts(gate_type == GateType::Interrupt)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 #[repr(u8)]
 pub enum InterruptIndex {
@@ -107,9 +164,18 @@ pub fn init_interrupts(memory_controller: &mut MemoryController) {
     IDT.load();
     info!("Loaded IDT");
 
+    lapic::init(memory_controller);
+
     unsafe { PICS.lock().initialize() }
     info!("Initialized PIC");
 
+    pit8254::init();
+    info!("Initialized PIT");
+
+    if let Some(lapic) = lapic::LAPIC.get() {
+        pit_monitor::init(lapic);
+    }
+
     Cpu::enable_interrupts();
     info!("Enabled interrupts");
 }
@@ -118,22 +184,42 @@ extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
     error!("Exception: Breakpoint\n{:#?}", stack_frame);
 }
 
+extern "x86-interrupt" fn debug_handler(mut stack_frame: InterruptStackFrame) {
+    single_step::handle(&mut stack_frame);
+}
+
+extern "x86-interrupt" fn divide_error_handler(mut stack_frame: InterruptStackFrame) {
+    let _ = writeln!(PanicSerial, "Exception: Divide error\n{:#?}", stack_frame);
+    exceptions::handle(ExceptionKind::DivideError, &mut stack_frame);
+}
+
+extern "x86-interrupt" fn general_protection_fault_handler(
+    mut stack_frame: InterruptStackFrame,
+    error_code: u64,
+) {
+    let _ = writeln!(
+        PanicSerial,
+        "Exception: General protection fault\n{:#?}\nError code: {:#x}",
+        stack_frame, error_code
+    );
+    exceptions::handle(ExceptionKind::GeneralProtection, &mut stack_frame);
+}
+
 extern "x86-interrupt" fn double_fault_handler(
     stack_frame: InterruptStackFrame,
     error_code: u64,
 ) -> ! {
-    error!("Exception: Double fault\n{:#?}", stack_frame);
-
-    // TODO: The following doesn't get executed for some reason sometimes
-    info!("Error code: {}", error_code);
-
-    info!("Halting CPU!");
-    loop {
-        hlt();
-    }
+    let _ = writeln!(
+        PanicSerial,
+        "Exception: Double fault\n{:#?}\nError code: {}\nHalting CPU!",
+        stack_frame, error_code
+    );
+    Cpu::halt();
 }
 
 extern "x86-interrupt" fn timer_interrupt_handler(_: InterruptStackFrame) {
+    pit8254::tick();
+
     unsafe {
         PICS.lock()
             .notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
@@ -144,7 +230,8 @@ extern "x86-interrupt" fn page_fault_handler(
     stack_frame: InterruptStackFrame,
     error_code: PageFaultErrorCode,
 ) {
-    error!(
+    let _ = writeln!(
+        PanicSerial,
         "Exception: Page fault\n{:#?}\nError code: {:?}",
         stack_frame, error_code
     );
@@ -163,3 +250,14 @@ extern "x86-interrupt" fn keyboard_interrupt_handler(_: InterruptStackFrame) {
             .notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8());
     }
 }
+
+extern "x86-interrupt" fn pit_monitor_interrupt_handler(_: InterruptStackFrame) {
+    pit_monitor::check();
+
+    if let Some(lapic) = lapic::LAPIC.get() {
+        lapic.eoi();
+    }
+}
+
+/// Spurious interrupts aren't real interrupts, so there's nothing to handle and no EOI to send
+extern "x86-interrupt" fn spurious_interrupt_handler(_: InterruptStackFrame) {}
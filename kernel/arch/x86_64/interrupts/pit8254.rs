@@ -0,0 +1,163 @@
+use crate::arch::cpu::Cpu;
+use core::sync::atomic::{fence, AtomicU32, AtomicU64, Ordering};
+use x86_64::instructions::port::Port;
+
+/// Frequency of the PIT's input clock in Hz
+pub const PIT_FREQUENCY: u32 = 1_193_182;
+
+/// Timer interrupt frequency used by [`init`]
+pub const DEFAULT_FREQUENCY_HZ: u32 = 1000;
+
+const CHANNEL_0_DATA_PORT: u16 = 0x40;
+const CHANNEL_2_DATA_PORT: u16 = 0x42;
+const COMMAND_PORT: u16 = 0x43;
+/// Also known as the PC speaker port. Bit 0 gates channel 2 and bit 5 reflects its output.
+const SPEAKER_CONTROL_PORT: u16 = 0x61;
+
+/// Number of timer interrupts since boot
+static TICKS: AtomicU64 = AtomicU64::new(0);
+/// Timer interrupt frequency channel 0 is programmed for. Firmware leaves it at ~18.2Hz.
+static FREQUENCY_HZ: AtomicU32 = AtomicU32::new(PIT_FREQUENCY / 65536);
+/// Milliseconds that passed before the last rate change
+static EPOCH_MS: AtomicU64 = AtomicU64::new(0);
+/// Value of `TICKS` at the last rate change, later ticks run at `FREQUENCY_HZ`
+static EPOCH_TICKS: AtomicU64 = AtomicU64::new(0);
+/// Odd while `init_with_hz` updates `FREQUENCY_HZ` and the epoch. Readers retry if it was
+/// odd or changed while they read them.
+static EPOCH_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Longest delay a single channel 2 countdown can cover(~54ms)
+const MAX_ONE_SHOT_MICROS: u64 = u16::MAX as u64 * 1_000_000 / PIT_FREQUENCY as u64;
+
+pub fn init() {
+    init_with_hz(DEFAULT_FREQUENCY_HZ);
+}
+
+/// Programs channel 0 to fire the timer interrupt roughly `hz` times per second.
+///
+/// The rate is limited by the 16 bit reload value and mode 3 needing at least 2, so it gets
+/// clamped to ~18.2Hz-596kHz. Uptime counted at the previous rate is kept.
+pub fn init_with_hz(hz: u32) {
+    let reload_value = reload_value_for_hz(hz);
+
+    // a tick between reading TICKS and storing the new rate would be counted at the wrong rate
+    Cpu::without_interrupts(|| {
+        EPOCH_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        let ticks = TICKS.load(Ordering::Relaxed);
+        let elapsed = ticks_to_ms(
+            ticks - EPOCH_TICKS.load(Ordering::Relaxed),
+            FREQUENCY_HZ.load(Ordering::Relaxed),
+        );
+        EPOCH_MS.fetch_add(elapsed, Ordering::Relaxed);
+        EPOCH_TICKS.store(ticks, Ordering::Relaxed);
+
+        let mut command: Port<u8> = Port::new(COMMAND_PORT);
+        let mut data: Port<u8> = Port::new(CHANNEL_0_DATA_PORT);
+        unsafe {
+            // channel 0, lobyte/hibyte access, mode 3(square wave generator), binary
+            command.write(0b0011_0110);
+            // 65536 is written as 0
+            data.write(reload_value as u8);
+            data.write((reload_value >> 8) as u8);
+        }
+
+        FREQUENCY_HZ.store(PIT_FREQUENCY / reload_value, Ordering::Relaxed);
+        EPOCH_SEQUENCE.fetch_add(1, Ordering::Release);
+    });
+}
+
+/// Channel 0 reload value for `hz`, clamped to what mode 3 can count(2-65536)
+pub fn reload_value_for_hz(hz: u32) -> u32 {
+    (PIT_FREQUENCY / hz.max(1)).clamp(2, 65536)
+}
+
+/// Called by the timer interrupt handler
+pub fn tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of timer interrupts since boot
+#[allow(unused)]
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Timer interrupt frequency channel 0 is programmed for, after clamping and rounding
+#[allow(unused)]
+pub fn frequency_hz() -> u32 {
+    FREQUENCY_HZ.load(Ordering::Relaxed)
+}
+
+/// Milliseconds since the timer interrupt got enabled
+#[allow(unused)]
+pub fn get_global_ms() -> u64 {
+    loop {
+        let sequence = EPOCH_SEQUENCE.load(Ordering::Acquire);
+        if sequence % 2 == 1 {
+            Cpu::spin_loop_hint();
+            continue;
+        }
+
+        let epoch_ms = EPOCH_MS.load(Ordering::Relaxed);
+        let epoch_ticks = EPOCH_TICKS.load(Ordering::Relaxed);
+        let hz = FREQUENCY_HZ.load(Ordering::Relaxed);
+        let ticks = TICKS.load(Ordering::Relaxed);
+
+        fence(Ordering::Acquire);
+        if EPOCH_SEQUENCE.load(Ordering::Relaxed) == sequence {
+            return epoch_ms + ticks_to_ms(ticks - epoch_ticks, hz);
+        }
+    }
+}
+
+/// Converts ticks at `hz` to milliseconds. The math is done in 128 bits so it can't
+/// overflow and there's no rounding until the very end.
+pub fn ticks_to_ms(ticks: u64, hz: u32) -> u64 {
+    (ticks as u128 * 1000 / hz.max(1) as u128) as u64
+}
+
+/// Busy waits for at least `micros` microseconds using channel 2 in one-shot mode.
+///
+/// This doesn't depend on the timer interrupt, so it can be used with interrupts disabled.
+#[allow(unused)]
+pub fn pit_udelay(micros: u32) {
+    let mut remaining = micros as u64;
+    while remaining > 0 {
+        let chunk = remaining.min(MAX_ONE_SHOT_MICROS);
+        one_shot(udelay_reload_value(chunk));
+        remaining -= chunk;
+    }
+}
+
+/// Converts a delay in microseconds to a channel 2 countdown value, rounding up
+fn udelay_reload_value(micros: u64) -> u16 {
+    let ticks = (micros * PIT_FREQUENCY as u64 + 999_999) / 1_000_000;
+    ticks.clamp(1, u16::MAX as u64) as u16
+}
+
+fn one_shot(reload_value: u16) {
+    let mut speaker_control: Port<u8> = Port::new(SPEAKER_CONTROL_PORT);
+    let mut command: Port<u8> = Port::new(COMMAND_PORT);
+    let mut data: Port<u8> = Port::new(CHANNEL_2_DATA_PORT);
+
+    unsafe {
+        // keep the gate low while programming and the speaker disconnected
+        let control = speaker_control.read() & !0b11;
+        speaker_control.write(control);
+
+        // channel 2, lobyte/hibyte access, mode 0(interrupt on terminal count), binary
+        command.write(0b1011_0000);
+        data.write(reload_value as u8);
+        data.write((reload_value >> 8) as u8);
+
+        // raising the gate starts the countdown, the output goes high once it reaches 0
+        speaker_control.write(control | 1);
+        while speaker_control.read() & (1 << 5) == 0 {
+            Cpu::spin_loop_hint();
+        }
+
+        speaker_control.write(control);
+    }
+}
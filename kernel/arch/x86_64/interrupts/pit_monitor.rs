@@ -0,0 +1,109 @@
+use crate::arch::cpu::Cpu;
+use crate::arch::interrupts::lapic::Lapic;
+use crate::arch::interrupts::pit8254;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// Vector of the local APIC timer interrupt that drives the monitor
+pub const PIT_MONITOR_VECTOR: u8 = 0xfe;
+
+/// How often the monitor checks on the PIT
+const PERIOD_MS: u32 = 1000;
+/// Length of the PIT delay the local APIC timer gets measured against
+const CALIBRATION_MS: u32 = 10;
+
+/// Divide configuration value for dividing the bus clock by 16
+const TIMER_DIVIDE_BY_16: u32 = 0b0011;
+/// LVT timer: periodic mode
+const LVT_TIMER_PERIODIC: u32 = 1 << 17;
+/// LVT: masked
+const LVT_MASKED: u32 = 1 << 16;
+
+static STALLS: StallDetector = StallDetector::new();
+
+/// Runs the local APIC timer every `PERIOD_MS` to check that the PIT keeps ticking.
+/// The local APIC timer doesn't go through the PIC, so it keeps running when the PIC stops
+/// delivering interrupts, e.g. after a missed EOI.
+///
+/// This isn't a watchdog for hangs with interrupts disabled. The LVT timer entry can only
+/// deliver a maskable interrupt, so a CPU spinning with IF=0 never runs the check.
+pub fn init(lapic: &Lapic) {
+    let ticks_per_ms = calibrate(lapic);
+    if ticks_per_ms == 0 {
+        warn!("Local APIC timer didn't count during calibration, not starting the PIT monitor");
+        return;
+    }
+
+    STALLS.reset(pit8254::ticks());
+    lapic.set_lvt_timer(LVT_TIMER_PERIODIC | PIT_MONITOR_VECTOR as u32);
+    lapic.set_timer_initial_count(ticks_per_ms.saturating_mul(PERIOD_MS));
+    info!(
+        "Started the PIT monitor, {} local APIC ticks/ms",
+        ticks_per_ms
+    );
+}
+
+/// Measures how many local APIC timer ticks fit in a millisecond
+fn calibrate(lapic: &Lapic) -> u32 {
+    lapic.set_lvt_timer(LVT_MASKED);
+    lapic.set_timer_divide(TIMER_DIVIDE_BY_16);
+    lapic.set_timer_initial_count(u32::MAX);
+
+    pit8254::pit_udelay(CALIBRATION_MS * 1000);
+
+    let elapsed = u32::MAX - lapic.timer_current_count();
+    lapic.set_timer_initial_count(0);
+
+    elapsed / CALIBRATION_MS
+}
+
+/// Called by the PIT monitor interrupt handler
+pub fn check() {
+    let ticks = pit8254::ticks();
+
+    if STALLS.observe(ticks) {
+        error!(
+            "PIT monitor: no timer interrupt in the last {}ms, stuck at {} ticks",
+            PERIOD_MS, ticks
+        );
+        Cpu::dump_registers();
+    }
+}
+
+/// Tracks the PIT tick count across checks
+pub struct StallDetector {
+    /// PIT ticks seen by the previous check
+    last_ticks: AtomicU64,
+    /// Set once a stall has been reported, so a stuck PIT doesn't flood the log
+    reported: AtomicBool,
+}
+
+impl StallDetector {
+    pub const fn new() -> Self {
+        Self {
+            last_ticks: AtomicU64::new(0),
+            reported: AtomicBool::new(false),
+        }
+    }
+
+    /// Starts over from `ticks` with nothing reported
+    pub fn reset(&self, ticks: u64) {
+        self.last_ticks.store(ticks, Ordering::SeqCst);
+        self.reported.store(false, Ordering::SeqCst);
+    }
+
+    /// Records the tick count of a check. Returns true if the PIT hasn't ticked since the
+    /// previous check and the stall hasn't been reported yet.
+    ///
+    /// The PIT fires every millisecond, so a whole period without a tick means the timer
+    /// interrupt isn't getting through.
+    pub fn observe(&self, ticks: u64) -> bool {
+        let previous = self.last_ticks.swap(ticks, Ordering::SeqCst);
+
+        if ticks != previous {
+            self.reported.store(false, Ordering::SeqCst);
+            return false;
+        }
+
+        !self.reported.swap(true, Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,84 @@
+use crate::arch::cpu::debug;
+use crate::logging::serial::PanicSerial;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::registers::rflags;
+use x86_64::structures::idt::InterruptStackFrame;
+
+/// RFLAGS.TF, raises a debug exception after every instruction
+const TRAP_FLAG: u64 = 1 << 8;
+
+/// RFLAGS.RF, suppresses instruction breakpoints for the next instruction
+const RESUME_FLAG: u64 = 1 << 16;
+
+/// Whether the debug exception handler keeps the trap flag set
+static STEPPING: AtomicBool = AtomicBool::new(false);
+
+/// Starts single-stepping the code that runs after this returns
+#[allow(unused)]
+pub fn start() {
+    STEPPING.store(true, Ordering::SeqCst);
+    unsafe {
+        rflags::write_raw(with_trap_flag(rflags::read_raw(), true));
+    }
+}
+
+/// Stops single-stepping at the next debug exception
+#[allow(unused)]
+pub fn stop() {
+    STEPPING.store(false, Ordering::SeqCst);
+}
+
+pub fn is_stepping() -> bool {
+    STEPPING.load(Ordering::SeqCst)
+}
+
+pub fn with_trap_flag(rflags: u64, enabled: bool) -> u64 {
+    if enabled {
+        rflags | TRAP_FLAG
+    } else {
+        rflags & !TRAP_FLAG
+    }
+}
+
+/// Sets or clears the trap flag in the RFLAGS that get restored when the handler returns
+pub fn set_trap_flag(stack_frame: &mut InterruptStackFrame, enabled: bool) {
+    unsafe {
+        stack_frame
+            .as_mut()
+            .update(|frame| frame.cpu_flags = with_trap_flag(frame.cpu_flags, enabled));
+    }
+}
+
+/// Reports the hardware breakpoint or step that raised the debug exception and keeps
+/// stepping until [`stop`] is called
+///
+/// Logs through `PanicSerial`, the debug exception can hit while the serial logger is locked.
+pub fn handle(stack_frame: &mut InterruptStackFrame) {
+    let rip = stack_frame.instruction_pointer.as_u64();
+
+    match debug::take_triggered_breakpoint() {
+        Some(slot) => {
+            let _ = writeln!(
+                PanicSerial,
+                "Hardware breakpoint {} hit: rip={:#x}",
+                slot, rip
+            );
+
+            // execution breakpoints fire before the instruction runs, without RF returning
+            // would hit the same breakpoint again right away
+            if debug::is_exec_breakpoint(slot) {
+                unsafe {
+                    stack_frame
+                        .as_mut()
+                        .update(|frame| frame.cpu_flags |= RESUME_FLAG);
+                }
+            }
+        }
+        None => {
+            let _ = writeln!(PanicSerial, "Step: rip={:#x}", rip);
+        }
+    }
+
+    set_trap_flag(stack_frame, is_stepping());
+}
@@ -0,0 +1,260 @@
+use crate::arch::x86_64::mm::area_frame_allocator::AreaFrameAllocator;
+use crate::arch::x86_64::mm::paging::entry::EntryFlags;
+use crate::arch::x86_64::mm::paging::mapper::Mapper;
+use crate::arch::x86_64::mm::paging::{InactivePageTable, Page};
+use crate::arch::x86_64::mm::{FrameAllocator, MemoryController};
+use crate::kutils::errors::ErrorCode;
+use spin::Mutex;
+
+/// Address spaces that were dropped, [`AddressSpace::reap_retired`] frees their frames
+static RETIRED: Mutex<Vec<Retired>> = Mutex::new(Vec::new());
+
+struct Retired {
+    table: InactivePageTable,
+    regions: Vec<Region>,
+    kernel_p4_slots: Vec<usize>,
+}
+
+/// A contiguous range of mapped pages in an [`AddressSpace`]
+#[derive(Debug, Clone, Copy)]
+pub struct Region {
+    start: Page,
+    end: Page,
+    flags: EntryFlags,
+}
+
+#[allow(unused)]
+impl Region {
+    pub fn start(&self) -> Page {
+        self.start
+    }
+
+    /// Last page of the region(inclusive)
+    pub fn end(&self) -> Page {
+        self.end
+    }
+
+    pub fn flags(&self) -> EntryFlags {
+        self.flags
+    }
+
+    fn overlaps(&self, start: Page, end: Page) -> bool {
+        self.start <= end && start <= self.end
+    }
+}
+
+/// Page table along with the regions mapped in it.
+///
+/// Kernel mappings are shared with every address space, so regions can only be mapped
+/// in P4 slots the kernel doesn't use.
+///
+/// Dropping it can't reach the memory controller, so its frames are freed by the next
+/// [`AddressSpace::new`] or [`AddressSpace::reap_retired`] instead.
+pub struct AddressSpace {
+    /// This address space's table, or while it's active the one that was active before
+    table: InactivePageTable,
+    active: bool,
+    regions: Vec<Region>,
+    /// P4 slots inherited from the kernel's page table
+    kernel_p4_slots: Vec<usize>,
+}
+
+#[allow(unused)]
+impl AddressSpace {
+    pub fn new(memory_controller: &mut MemoryController) -> Self {
+        Self::reap_retired(memory_controller);
+
+        let MemoryController {
+            active_table,
+            frame_allocator,
+            temporary_page,
+            ..
+        } = memory_controller;
+
+        // the last entry is the recursive mapping, which `InactivePageTable::new` sets up
+        let kernel_entries = (0..511)
+            .filter_map(|idx| {
+                let entry = &active_table.p4()[idx];
+                entry
+                    .pointed_frame()
+                    .map(|frame| (idx, frame, entry.flags()))
+            })
+            .collect::<Vec<_>>();
+
+        let frame = frame_allocator
+            .allocate_frame()
+            .expect("no frames available");
+        let mut table = InactivePageTable::new(frame, active_table, temporary_page);
+
+        let mut kernel_p4_slots = vec![];
+        active_table.with(&mut table, temporary_page, |mapper| {
+            for (idx, frame, flags) in kernel_entries {
+                mapper.p4_mut()[idx].set(frame, flags);
+                kernel_p4_slots.push(idx);
+            }
+        });
+
+        Self {
+            table,
+            active: false,
+            regions: vec![],
+            kernel_p4_slots,
+        }
+    }
+
+    /// Frees the frames of every dropped address space: the mapped pages, the page tables
+    /// below the P4 slots it owns and the P4 table itself
+    pub fn reap_retired(memory_controller: &mut MemoryController) {
+        let retired = core::mem::take(&mut *RETIRED.lock());
+
+        let MemoryController {
+            active_table,
+            frame_allocator,
+            temporary_page,
+            ..
+        } = memory_controller;
+        for mut space in retired {
+            active_table.with(&mut space.table, temporary_page, |mapper| {
+                for region in &space.regions {
+                    for page in Page::range_inclusive(region.start, region.end) {
+                        mapper.unmap(page, frame_allocator);
+                    }
+                }
+
+                // the last slot is the recursive mapping
+                for idx in (0..511).filter(|idx| !space.kernel_p4_slots.contains(idx)) {
+                    free_page_tables(mapper, idx, frame_allocator);
+                }
+            });
+
+            frame_allocator.deallocate_frame(space.table.p4_frame());
+        }
+    }
+
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// Maps `start..=end` to newly allocated frames
+    pub fn map_region(
+        &mut self,
+        memory_controller: &mut MemoryController,
+        start: Page,
+        end: Page,
+        flags: EntryFlags,
+    ) -> Result<(), ErrorCode> {
+        if start > end {
+            return Err(ErrorCode::EINVAL);
+        }
+        if (start.p4_index()..=end.p4_index()).any(|idx| self.kernel_p4_slots.contains(&idx)) {
+            return Err(ErrorCode::EFAULT);
+        }
+        if self
+            .regions
+            .iter()
+            .any(|region| region.overlaps(start, end))
+        {
+            return Err(ErrorCode::EEXIST);
+        }
+
+        self.with_mapper(memory_controller, |mapper, frame_allocator| {
+            for page in Page::range_inclusive(start, end) {
+                mapper.map(page, flags, frame_allocator);
+            }
+        });
+        self.regions.push(Region { start, end, flags });
+
+        Ok(())
+    }
+
+    /// Unmaps the region starting at `start` and frees its frames
+    pub fn unmap_region(
+        &mut self,
+        memory_controller: &mut MemoryController,
+        start: Page,
+    ) -> Result<(), ErrorCode> {
+        let Some(idx) = self.regions.iter().position(|region| region.start == start) else {
+            return Err(ErrorCode::ENOENT);
+        };
+        let region = self.regions.remove(idx);
+
+        self.with_mapper(memory_controller, |mapper, frame_allocator| {
+            for page in Page::range_inclusive(region.start, region.end) {
+                mapper.unmap(page, frame_allocator);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Makes this address space the active one. The previously active table is kept until
+    /// [`AddressSpace::switch_back`].
+    pub fn switch_to(&mut self, memory_controller: &mut MemoryController) {
+        assert!(!self.active, "address space is already active");
+
+        self.table = memory_controller.active_table.switch(&mut self.table);
+        self.active = true;
+    }
+
+    /// Makes the table that was active before [`AddressSpace::switch_to`] active again
+    pub fn switch_back(&mut self, memory_controller: &mut MemoryController) {
+        assert!(self.active, "address space isn't active");
+
+        self.table = memory_controller.active_table.switch(&mut self.table);
+        self.active = false;
+    }
+
+    /// Runs `f` on this address space's table, which is the active one after `switch_to`
+    fn with_mapper<F>(&mut self, memory_controller: &mut MemoryController, f: F)
+    where
+        F: FnOnce(&mut Mapper, &mut AreaFrameAllocator),
+    {
+        let MemoryController {
+            active_table,
+            frame_allocator,
+            temporary_page,
+            ..
+        } = memory_controller;
+
+        if self.active {
+            f(active_table, frame_allocator);
+        } else {
+            active_table.with(&mut self.table, temporary_page, |mapper| {
+                f(mapper, frame_allocator)
+            });
+        }
+    }
+}
+
+impl Drop for AddressSpace {
+    fn drop(&mut self) {
+        // the table we hold is someone else's while active
+        assert!(!self.active, "dropped an active address space");
+
+        RETIRED.lock().push(Retired {
+            // SAFETY: the table has no drop glue and isn't touched again after this
+            table: unsafe { core::ptr::read(&self.table) },
+            regions: core::mem::take(&mut self.regions),
+            kernel_p4_slots: core::mem::take(&mut self.kernel_p4_slots),
+        });
+    }
+}
+
+/// Frees the P3, P2 and P1 tables below `p4_idx`. The pages they map have to be unmapped.
+fn free_page_tables(mapper: &mut Mapper, p4_idx: usize, frame_allocator: &mut AreaFrameAllocator) {
+    let mut frames = vec![];
+
+    if let Some(p3) = mapper.p4_mut().next_table_mut(p4_idx) {
+        for p3_idx in 0..512 {
+            if let Some(p2) = p3.next_table_mut(p3_idx) {
+                frames.extend((0..512).filter_map(|p2_idx| p2[p2_idx].pointed_frame()));
+            }
+        }
+        frames.extend((0..512).filter_map(|p3_idx| p3[p3_idx].pointed_frame()));
+    }
+    frames.extend(mapper.p4()[p4_idx].pointed_frame());
+
+    for frame in frames {
+        frame_allocator.deallocate_frame(frame);
+    }
+}
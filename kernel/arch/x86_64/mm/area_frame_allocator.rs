@@ -1,6 +1,31 @@
-use crate::arch::x86_64::mm::{Frame, FrameAllocator};
+use crate::arch::x86_64::mm::{Frame, FrameAllocator, PAGE_SIZE};
+use crate::kutils::errors::ErrorCode;
+use crate::kutils::units::GiB;
+use core::ptr::addr_of_mut;
+use core::sync::atomic::{AtomicBool, Ordering};
 use multiboot2::{MemoryArea, MemoryMapTag};
 
+/// Physical memory the allocator keeps per-frame state for. Frames above it aren't handed out.
+const MAX_PHYSICAL_MEMORY: usize = 4 * GiB;
+const MAX_FRAMES: usize = MAX_PHYSICAL_MEMORY / PAGE_SIZE;
+/// Ranges `reserve_range` can hold on top of the kernel, multiboot info and initrd
+const MAX_RESERVED_RANGES: usize = 32;
+
+/// Per-frame bookkeeping. It's part of the kernel image rather than the heap, the heap is
+/// backed by frames from this allocator and doesn't exist yet when it's created.
+struct FrameState {
+    /// References to each frame, 0 for frames that aren't handed out
+    ref_counts: [u16; MAX_FRAMES],
+    /// One bit per frame that was handed out and freed again, `allocate_frame` reuses them first
+    freed: [u64; MAX_FRAMES / 64],
+}
+
+static mut FRAME_STATE: FrameState = FrameState {
+    ref_counts: [0; MAX_FRAMES],
+    freed: [0; MAX_FRAMES / 64],
+};
+static FRAME_STATE_TAKEN: AtomicBool = AtomicBool::new(false);
+
 pub struct AreaFrameAllocator<'a> {
     next_free_frame: Frame,
     current_area: Option<&'a MemoryArea>,
@@ -9,16 +34,36 @@ pub struct AreaFrameAllocator<'a> {
     kernel_end: Frame,
     multiboot_start: Frame,
     multiboot_end: Frame,
+    /// First and last frame of the initrd module, if the bootloader loaded one
+    initrd: Option<(Frame, Frame)>,
+    state: &'static mut FrameState,
+    /// Set bits in `state.freed`
+    freed_frames: usize,
+    /// No word of `state.freed` below this one has a bit set
+    freed_hint: usize,
+    /// First and last frame numbers of ranges claimed through `reserve_range`, e.g. device
+    /// memory. Only the first `reserved_count` are in use.
+    reserved: [(usize, usize); MAX_RESERVED_RANGES],
+    reserved_count: usize,
+    /// Frames currently handed out
+    allocated_frames: usize,
 }
 
 impl<'a> AreaFrameAllocator<'a> {
+    /// Creates the frame allocator. There can only be one since they share the per-frame state.
     pub fn new(
         kernel_start: usize,
         kernel_end: usize,
         multiboot_start: usize,
         multiboot_end: usize,
+        initrd: Option<(usize, usize)>,
         memory_map_tag: &'a MemoryMapTag,
     ) -> Self {
+        assert!(
+            !FRAME_STATE_TAKEN.swap(true, Ordering::Relaxed),
+            "there can only be one frame allocator"
+        );
+
         let mut alloc = Self {
             next_free_frame: Frame::containing_address(0),
             current_area: None,
@@ -27,12 +72,168 @@ impl<'a> AreaFrameAllocator<'a> {
             kernel_end: Frame::containing_address(kernel_end),
             multiboot_start: Frame::containing_address(multiboot_start),
             multiboot_end: Frame::containing_address(multiboot_end),
+            initrd: initrd.map(|(start, end)| {
+                (
+                    Frame::containing_address(start),
+                    Frame::containing_address(end),
+                )
+            }),
+            // SAFETY: the flag above makes sure this is the only reference
+            state: unsafe { &mut *addr_of_mut!(FRAME_STATE) },
+            freed_frames: 0,
+            freed_hint: 0,
+            reserved: [(0, 0); MAX_RESERVED_RANGES],
+            reserved_count: 0,
+            allocated_frames: 0,
         };
 
         alloc.choose_next_area();
         alloc
     }
 
+    /// Adds a reference to an allocated `frame`, e.g. when it gets mapped into another
+    /// address space. Returns the new reference count.
+    #[allow(unused)]
+    pub fn inc_ref(&mut self, frame: &Frame) -> usize {
+        let count = &mut self.state.ref_counts[frame.number];
+        assert!(*count > 0, "{:?} isn't allocated", frame);
+
+        *count = count
+            .checked_add(1)
+            .unwrap_or_else(|| panic!("too many references to {:?}", frame));
+        *count as usize
+    }
+
+    /// References to `frame`, 0 if it isn't handed out
+    pub fn ref_count(&self, frame: &Frame) -> usize {
+        self.state
+            .ref_counts
+            .get(frame.number)
+            .map_or(0, |count| *count as usize)
+    }
+
+    /// Drops a reference to `frame` and returns the number of references left
+    pub fn dec_ref(&mut self, frame: &Frame) -> usize {
+        let Some(count) = self.state.ref_counts.get_mut(frame.number) else {
+            return 0;
+        };
+
+        *count = count.saturating_sub(1);
+        *count as usize
+    }
+
+    /// Keeps `start..=end` from ever being allocated. Returns whether a new reservation was
+    /// added, reserving a range that's already reserved does nothing. Fails with `EBUSY` if
+    /// some of it may already be handed out and with `ENOMEM` once there are too many ranges.
+    pub fn reserve_range(&mut self, start: Frame, end: Frame) -> Result<bool, ErrorCode> {
+        if start > end {
+            return Err(ErrorCode::EINVAL);
+        }
+        if self.reserved[..self.reserved_count]
+            .iter()
+            .any(|(reserved_start, reserved_end)| {
+                *reserved_start <= start.number && end.number <= *reserved_end
+            })
+        {
+            return Ok(false);
+        }
+        if self.reserved_count == MAX_RESERVED_RANGES {
+            return Err(ErrorCode::ENOMEM);
+        }
+
+        // frames past the tracked memory are never handed out
+        let tracked = start.number.min(MAX_FRAMES)..(end.number + 1).min(MAX_FRAMES);
+        if self.state.ref_counts[tracked.clone()]
+            .iter()
+            .any(|count| *count > 0)
+        {
+            return Err(ErrorCode::EBUSY);
+        }
+
+        // freed frames in the range must not be handed out again either
+        for number in tracked {
+            let (idx, bit) = (number / 64, 1 << (number % 64));
+            if self.state.freed[idx] & bit != 0 {
+                self.state.freed[idx] &= !bit;
+                self.freed_frames -= 1;
+            }
+        }
+
+        self.reserved[self.reserved_count] = (start.number, end.number);
+        self.reserved_count += 1;
+        Ok(true)
+    }
+
+    /// Drops the reservation `reserve_range` added for exactly `start..=end`
+    pub fn release_range(&mut self, start: Frame, end: Frame) {
+        let Some(idx) = self.reserved[..self.reserved_count]
+            .iter()
+            .position(|range| *range == (start.number, end.number))
+        else {
+            return;
+        };
+
+        self.reserved_count -= 1;
+        self.reserved[idx] = self.reserved[self.reserved_count];
+    }
+
+    /// Usable areas of the memory map as inclusive `(start, end)` physical addresses
+    pub fn usable_areas(&self) -> Vec<(usize, usize)> {
+        self.memory_map_tag
+            .memory_areas()
+            .map(|area| {
+                (
+                    area.start_address() as usize,
+                    (area.start_address() + area.size() - 1) as usize,
+                )
+            })
+            .collect()
+    }
+
+    /// Whether `frame` is never handed out: kernel, multiboot info, initrd or reserved ranges
+    pub fn is_reserved(&self, frame: &Frame) -> bool {
+        self.reserved_range_containing(frame).is_some()
+    }
+
+    /// Last frame of the reserved range `frame` falls in
+    fn reserved_range_containing(&self, frame: &Frame) -> Option<Frame> {
+        [
+            Some((&self.kernel_start, &self.kernel_end)),
+            Some((&self.multiboot_start, &self.multiboot_end)),
+            self.initrd.as_ref().map(|(start, end)| (start, end)),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|(start, end)| (start.number, end.number))
+        .chain(self.reserved[..self.reserved_count].iter().copied())
+        .find(|(start, end)| *start <= frame.number && frame.number <= *end)
+        .map(|(_, end)| Frame { number: end })
+    }
+
+    pub fn allocated_frames(&self) -> usize {
+        self.allocated_frames
+    }
+
+    /// Frames in all usable memory areas
+    pub fn total_frames(&self) -> usize {
+        self.memory_map_tag
+            .memory_areas()
+            .map(|area| area.size() as usize / PAGE_SIZE)
+            .sum()
+    }
+
+    /// Frames taken by the kernel, multiboot info and initrd, which are never handed out
+    pub fn reserved_frames(&self) -> usize {
+        let frame_count = |start: &Frame, end: &Frame| end.number - start.number + 1;
+
+        frame_count(&self.kernel_start, &self.kernel_end)
+            + frame_count(&self.multiboot_start, &self.multiboot_end)
+            + self
+                .initrd
+                .as_ref()
+                .map_or(0, |(start, end)| frame_count(start, end))
+    }
+
     fn choose_next_area(&mut self) {
         self.current_area = self
             .memory_map_tag
@@ -50,42 +251,80 @@ impl<'a> AreaFrameAllocator<'a> {
             }
         }
     }
+
+    /// Takes the lowest frame that was freed before
+    fn take_freed_frame(&mut self) -> Option<Frame> {
+        if self.freed_frames == 0 {
+            return None;
+        }
+
+        let (idx, word) = self
+            .state
+            .freed
+            .iter_mut()
+            .enumerate()
+            .skip(self.freed_hint)
+            .find(|(_, word)| **word != 0)?;
+        let bit = word.trailing_zeros() as usize;
+        *word &= !(1 << bit);
+
+        self.freed_hint = idx;
+        self.freed_frames -= 1;
+        Some(Frame {
+            number: idx * 64 + bit,
+        })
+    }
+
+    /// Takes the next frame of the memory map that was never handed out
+    fn take_next_frame(&mut self) -> Option<Frame> {
+        let area = self.current_area?;
+        let frame = self.next_free_frame.clone();
+        if frame.number >= MAX_FRAMES {
+            return None;
+        }
+
+        let current_area_last_frame = {
+            let address = area.start_address() + area.size() - 1;
+            Frame::containing_address(address as usize)
+        };
+
+        if frame > current_area_last_frame {
+            self.choose_next_area();
+        } else if let Some(reserved_end) = self.reserved_range_containing(&frame) {
+            self.next_free_frame = reserved_end.next();
+        } else {
+            self.next_free_frame = frame.next();
+            return Some(frame);
+        }
+
+        self.take_next_frame()
+    }
 }
 
 impl<'a> FrameAllocator for AreaFrameAllocator<'a> {
     fn allocate_frame(&mut self) -> Option<Frame> {
-        if let Some(area) = self.current_area {
-            let frame = Frame {
-                number: self.next_free_frame.number,
-            };
+        let frame = self.take_freed_frame().or_else(|| self.take_next_frame())?;
 
-            let current_area_last_frame = {
-                let address = area.start_address() + area.size() - 1;
-                Frame::containing_address(address as usize)
-            };
-
-            if frame > current_area_last_frame {
-                self.choose_next_area();
-            } else if frame >= self.kernel_start && frame <= self.kernel_end {
-                self.next_free_frame = Frame {
-                    number: self.kernel_end.number + 1,
-                };
-            } else if frame >= self.multiboot_start && frame <= self.multiboot_end {
-                self.next_free_frame = Frame {
-                    number: self.multiboot_end.number + 1,
-                };
-            } else {
-                self.next_free_frame.number += 1;
-                return Some(frame);
-            }
+        self.state.ref_counts[frame.number] = 1;
+        self.allocated_frames += 1;
+        Some(frame)
+    }
 
-            self.allocate_frame()
-        } else {
-            None
+    fn deallocate_frame(&mut self, frame: Frame) {
+        // reserved and device frames are never handed out, so this also rejects them
+        if self.ref_count(&frame) == 0 {
+            error!("{:?} isn't allocated, not freeing it", frame);
+            return;
+        }
+        if self.dec_ref(&frame) > 0 {
+            // still mapped somewhere else
+            return;
         }
-    }
 
-    fn deallocate_frame(&mut self, _frame: Frame) {
-        // TODO
+        let idx = frame.number / 64;
+        self.state.freed[idx] |= 1 << (frame.number % 64);
+        self.freed_frames += 1;
+        self.freed_hint = self.freed_hint.min(idx);
+        self.allocated_frames -= 1;
     }
 }
@@ -1,20 +1,28 @@
 use crate::arch::x86_64::mm::area_frame_allocator::AreaFrameAllocator;
-use crate::arch::x86_64::mm::paging::entry::EntryFlags;
-use crate::arch::x86_64::mm::paging::{Page, PhysicalAddress};
+use crate::arch::x86_64::mm::paging::entry::{CacheMode, EntryFlags};
+use crate::arch::x86_64::mm::paging::temporary_page::TemporaryPage;
+use crate::arch::x86_64::mm::paging::{
+    Page, PhysicalAddress, VirtualAddress, TEMPORARY_PAGE_ADDRESS,
+};
 use crate::arch::x86_64::mm::stack_allocator::{Stack, StackAllocator};
-use crate::kutils::multiboot::get_kernel_start_end;
+use crate::kutils::errors::ErrorCode;
+use crate::kutils::multiboot::{get_initrd_start_end, get_kernel_start_end};
+use crate::kutils::units::{format_bytes, MiB};
+use core::fmt::{Debug, Display, Formatter};
+use core::ops::Add;
 use linked_list_allocator::LockedHeap;
 use multiboot2::BootInformation;
 use once::assert_has_not_been_called;
 
+pub mod address_space;
 pub mod area_frame_allocator;
 pub mod paging;
+#[cfg(any(feature = "mm-self-test", feature = "test-mode"))]
+mod self_test;
 pub mod stack_allocator;
 
 pub const HEAP_START: usize = 0o0_000_010_000_000_000;
-pub const HEAP_SIZE: usize = 100 * 1024 * 1024; // 100 MiB
-#[allow(non_upper_case_globals)]
-pub const MiB: usize = 1024 * 1024;
+pub const HEAP_SIZE: usize = 100 * MiB;
 
 #[global_allocator]
 pub static HEAP_ALLOCATOR: LockedHeap = LockedHeap::empty();
@@ -23,16 +31,47 @@ pub fn display_heap_stats() {
     let alloc = HEAP_ALLOCATOR.lock();
 
     info!(
-        "Heap size: {}MiB, Free: {}MiB",
-        alloc.size() / MiB,
-        alloc.free() / MiB
+        "Heap size: {}, Free: {}",
+        format_bytes(alloc.size()),
+        format_bytes(alloc.free())
     );
 }
 
+/// Physical memory and kernel heap usage, in bytes
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MemStats {
+    pub total: usize,
+    pub free: usize,
+    pub used: usize,
+    pub allocated_frames: usize,
+    pub heap_used: usize,
+    pub heap_free: usize,
+}
+
+impl Display for MemStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        writeln!(
+            f,
+            "physical: {} total, {} used, {} free",
+            format_bytes(self.total),
+            format_bytes(self.used),
+            format_bytes(self.free)
+        )?;
+        writeln!(f, "frames:   {} allocated", self.allocated_frames)?;
+        writeln!(
+            f,
+            "heap:     {} used, {} free",
+            format_bytes(self.heap_used),
+            format_bytes(self.heap_free)
+        )
+    }
+}
+
 pub struct MemoryController<'a> {
     active_table: paging::ActivePageTable,
     frame_allocator: AreaFrameAllocator<'a>,
     stack_allocator: StackAllocator,
+    temporary_page: TemporaryPage,
 }
 
 impl<'a> MemoryController<'a> {
@@ -44,17 +83,137 @@ impl<'a> MemoryController<'a> {
             flags,
         )
     }
+
+    /// Keeps the frames covering `start..=end` from being allocated, see
+    /// [`AreaFrameAllocator::reserve_range`]
+    #[allow(unused)]
+    pub fn reserve_range(
+        &mut self,
+        start: PhysicalAddress,
+        end: PhysicalAddress,
+    ) -> Result<(), ErrorCode> {
+        self.frame_allocator
+            .reserve_range(
+                Frame::containing_address(start),
+                Frame::containing_address(end),
+            )
+            .map(|_| ())
+    }
+
+    #[allow(unused)]
+    pub fn is_reserved(&self, address: PhysicalAddress) -> bool {
+        self.frame_allocator
+            .is_reserved(&Frame::containing_address(address))
+    }
+
+    /// Whether `start..start + len` lies entirely in usable RAM according to the memory map
+    pub fn is_in_memory_map(&self, start: PhysicalAddress, len: usize) -> bool {
+        range_in_areas(&self.frame_allocator.usable_areas(), start, len)
+    }
+
+    /// Reserves the frames of a device's registers at `phys..phys + size` and identity maps
+    /// them uncacheable and non-executable. Returns the virtual address of `phys`.
+    ///
+    /// Fails with `EEXIST` if a page in the range is already mapped some other way. Nothing
+    /// stays mapped or reserved then.
+    pub fn map_mmio(
+        &mut self,
+        phys: PhysicalAddress,
+        size: usize,
+    ) -> Result<VirtualAddress, ErrorCode> {
+        if size == 0 {
+            return Err(ErrorCode::EINVAL);
+        }
+
+        let start = Frame::containing_address(phys);
+        let end = Frame::containing_address(phys + size - 1);
+        let newly_reserved = self
+            .frame_allocator
+            .reserve_range(start.clone(), end.clone())?;
+        let flags = EntryFlags::WRITABLE
+            | EntryFlags::NO_EXECUTE
+            | EntryFlags::from_cache_mode(CacheMode::Uncacheable);
+
+        let mut mapped = vec![];
+        for frame in Frame::range_inclusive(start.clone(), end.clone()) {
+            let page = Page::containing_address(frame.start_address());
+            let was_mapped = self.active_table.translate_page(page).is_some();
+
+            if self
+                .active_table
+                .identity_map_if_needed(frame, flags, &mut self.frame_allocator)
+                .is_err()
+            {
+                // undo the part of the range this call mapped
+                for page in mapped {
+                    self.active_table.unmap_frame(page);
+                }
+                if newly_reserved {
+                    self.frame_allocator.release_range(start, end);
+                }
+                return Err(ErrorCode::EEXIST);
+            }
+            if !was_mapped {
+                mapped.push(page);
+            }
+        }
+
+        Ok(phys)
+    }
+
+    /// Memory usage across the frame allocator and the kernel heap.
+    /// Reserved frames (kernel, multiboot info, initrd) count as used.
+    pub fn stats(&self) -> MemStats {
+        let total_frames = self.frame_allocator.total_frames();
+        let allocated_frames = self.frame_allocator.allocated_frames();
+        let used_frames =
+            (allocated_frames + self.frame_allocator.reserved_frames()).min(total_frames);
+        let heap = HEAP_ALLOCATOR.lock();
+
+        MemStats {
+            total: total_frames * PAGE_SIZE,
+            free: (total_frames - used_frames) * PAGE_SIZE,
+            used: used_frames * PAGE_SIZE,
+            allocated_frames,
+            heap_used: heap.used(),
+            heap_free: heap.free(),
+        }
+    }
+
+    /// Maps the frame containing `phys` at the temporary page and runs `f` over its 4KiB.
+    /// The frame is unmapped again afterwards, but not freed.
+    pub fn with_phys_mapped<R>(
+        &mut self,
+        phys: PhysicalAddress,
+        f: impl FnOnce(&mut [u8]) -> R,
+    ) -> R {
+        let frame = Frame::containing_address(phys);
+        let window_start = self.temporary_page.map(frame, &mut self.active_table);
+
+        // SAFETY: the temporary page maps exactly one writable frame until we unmap it
+        let window = unsafe { core::slice::from_raw_parts_mut(window_start as *mut u8, PAGE_SIZE) };
+        let result = f(window);
+
+        self.temporary_page.unmap(&mut self.active_table);
+        result
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
 pub struct Frame {
     number: usize,
 }
 
+impl Debug for Frame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Frame(phys={:#x})", self.start_address())
+    }
+}
+
 pub const PAGE_SIZE: usize = 4096;
 
 impl Frame {
-    fn containing_address(address: usize) -> Self {
+    pub fn containing_address(address: usize) -> Self {
         Self {
             number: address / PAGE_SIZE,
         }
@@ -73,6 +232,21 @@ impl Frame {
             number: self.number,
         }
     }
+
+    /// The frame right after this one
+    pub fn next(&self) -> Frame {
+        self.clone() + 1
+    }
+}
+
+impl Add<usize> for Frame {
+    type Output = Frame;
+
+    fn add(self, rhs: usize) -> Self::Output {
+        Frame {
+            number: self.number + rhs,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -86,9 +260,8 @@ impl Iterator for FrameIter {
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.start <= self.end {
-            let frame = self.start.clone();
-            self.start.number += 1;
-            Some(frame)
+            let next = self.start.next();
+            Some(core::mem::replace(&mut self.start, next))
         } else {
             None
         }
@@ -111,15 +284,36 @@ pub fn init(boot_info: &BootInformation) -> MemoryController {
         boot_info.end_address()
     );
 
+    let initrd = get_initrd_start_end(boot_info);
+    if let Some((initrd_start, initrd_end)) = initrd {
+        info!(
+            "initrd start: {:#x}, initrd end: {:#x}",
+            initrd_start, initrd_end
+        );
+    }
+
     let mut frame_allocator = AreaFrameAllocator::new(
         kernel_start,
         kernel_end,
         boot_info.start_address(),
         boot_info.end_address(),
+        initrd,
         memory_map_tag,
     );
 
-    let mut active_table = paging::remap_the_kernel(&mut frame_allocator, boot_info);
+    let mut temporary_page = TemporaryPage::new(
+        Page::containing_address(TEMPORARY_PAGE_ADDRESS),
+        &mut frame_allocator,
+    );
+    let mut active_table =
+        paging::remap_the_kernel(&mut frame_allocator, &mut temporary_page, boot_info);
+
+    // The boot temporary page used up its frames on page tables in the boot P4, which the
+    // new P4 doesn't have. Give the new one fresh frames for its own tables.
+    let temporary_page = TemporaryPage::new(
+        Page::containing_address(TEMPORARY_PAGE_ADDRESS),
+        &mut frame_allocator,
+    );
     let heap_start_page = Page::containing_address(HEAP_START);
     let heap_end_page = Page::containing_address(HEAP_START + HEAP_SIZE - 1);
 
@@ -138,7 +332,34 @@ pub fn init(boot_info: &BootInformation) -> MemoryController {
         active_table,
         frame_allocator,
         stack_allocator,
+        temporary_page,
+    }
+}
+
+/// Whether every frame of `start..start + len` lies in one of `areas`, which are inclusive
+/// `(start, end)` physical ranges. Empty and overflowing ranges are never in.
+pub fn range_in_areas(
+    areas: &[(PhysicalAddress, PhysicalAddress)],
+    start: PhysicalAddress,
+    len: usize,
+) -> bool {
+    if len == 0 {
+        return false;
     }
+    let Some(end) = start.checked_add(len - 1) else {
+        return false;
+    };
+
+    Frame::range_inclusive(
+        Frame::containing_address(start),
+        Frame::containing_address(end),
+    )
+    .all(|frame| {
+        areas.iter().any(|(area_start, area_end)| {
+            Frame::containing_address(*area_start) <= frame
+                && frame <= Frame::containing_address(*area_end)
+        })
+    })
 }
 
 pub trait FrameAllocator {
@@ -65,4 +65,24 @@ impl EntryFlags {
 
         flags
     }
+
+    /// Returns the PWT/PCD bits selecting `mode` in the PAT.
+    ///
+    /// This relies on the PAT layout programmed by [`crate::arch::x86_64::utils::init_pat`].
+    pub fn from_cache_mode(mode: CacheMode) -> EntryFlags {
+        match mode {
+            CacheMode::WriteBack => EntryFlags::empty(),
+            CacheMode::WriteCombining => EntryFlags::WRITE_THROUGH,
+            CacheMode::Uncacheable => EntryFlags::WRITE_THROUGH | EntryFlags::NO_CACHE,
+        }
+    }
+}
+
+/// Memory type used by the CPU when accessing a mapped page
+#[allow(unused)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CacheMode {
+    WriteBack,
+    WriteCombining,
+    Uncacheable,
 }
@@ -3,7 +3,14 @@ use crate::arch::x86_64::mm::paging::table::{Level4, Table, P4};
 use crate::arch::x86_64::mm::paging::{Page, PhysicalAddress, VirtualAddress, ENTRY_COUNT};
 use crate::arch::x86_64::mm::FrameAllocator;
 use crate::arch::x86_64::mm::{Frame, PAGE_SIZE};
-use x86_64::VirtAddr;
+use crate::arch::x86_64::utils::{flush_tlb, flush_tlb_range};
+use crate::kutils::align::is_aligned;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MapError {
+    /// The page is already mapped to some frame
+    AlreadyMapped,
+}
 
 pub struct Mapper {
     p4: &'static mut Table<Level4>,
@@ -23,12 +30,26 @@ impl Mapper {
     }
 
     pub fn translate(&self, virtual_address: VirtualAddress) -> Option<PhysicalAddress> {
+        self.translate_with_flags(virtual_address)
+            .map(|(physical_address, _)| physical_address)
+    }
+
+    /// Like [`Mapper::translate`], but also returns the flags of the entry that maps the
+    /// address
+    pub fn translate_with_flags(
+        &self,
+        virtual_address: VirtualAddress,
+    ) -> Option<(PhysicalAddress, EntryFlags)> {
         let offset = virtual_address % PAGE_SIZE;
-        self.translate_page(Page::containing_address(virtual_address))
-            .map(|frame| frame.number * PAGE_SIZE + offset)
+        self.translate_page_with_flags(Page::containing_address(virtual_address))
+            .map(|(frame, flags)| (frame.number * PAGE_SIZE + offset, flags))
     }
 
     pub fn translate_page(&self, page: Page) -> Option<Frame> {
+        self.translate_page_with_flags(page).map(|(frame, _)| frame)
+    }
+
+    pub fn translate_page_with_flags(&self, page: Page) -> Option<(Frame, EntryFlags)> {
         let p3 = self.p4().next_table(page.p4_index());
 
         let huge_page = || {
@@ -39,12 +60,11 @@ impl Mapper {
                 if let Some(start_frame) = p3_entry.pointed_frame() {
                     if p3_entry.flags().contains(EntryFlags::HUGE_PAGE) {
                         // address must be 1GiB aligned
-                        assert_eq!(start_frame.number % (ENTRY_COUNT * ENTRY_COUNT), 0);
-                        return Some(Frame {
-                            number: start_frame.number
-                                + page.p2_index() * ENTRY_COUNT
-                                + page.p1_index(),
-                        });
+                        assert!(is_aligned(start_frame.number, ENTRY_COUNT * ENTRY_COUNT));
+                        return Some((
+                            start_frame + page.p2_index() * ENTRY_COUNT + page.p1_index(),
+                            p3_entry.flags(),
+                        ));
                     }
                 }
 
@@ -55,10 +75,8 @@ impl Mapper {
                     if let Some(start_frame) = p2_entry.pointed_frame() {
                         if p2_entry.flags().contains(EntryFlags::HUGE_PAGE) {
                             // address must be 2MiB aligned
-                            assert_eq!(start_frame.number % ENTRY_COUNT, 0);
-                            return Some(Frame {
-                                number: start_frame.number + page.p1_index(),
-                            });
+                            assert!(is_aligned(start_frame.number, ENTRY_COUNT));
+                            return Some((start_frame + page.p1_index(), p2_entry.flags()));
                         }
                     }
                 }
@@ -69,11 +87,57 @@ impl Mapper {
 
         p3.and_then(|p3| p3.next_table(page.p3_index()))
             .and_then(|p2| p2.next_table(page.p2_index()))
-            .and_then(|p1| p1[page.p1_index()].pointed_frame())
+            .and_then(|p1| {
+                let entry = &p1[page.p1_index()];
+                entry.pointed_frame().map(|frame| (frame, entry.flags()))
+            })
             .or_else(huge_page)
     }
 
-    pub fn map_to<A>(&mut self, page: Page, frame: Frame, flags: EntryFlags, allocator: &mut A)
+    /// Prints every mapping in `start..end` to serial, merging consecutive pages that are
+    /// physically contiguous and have the same flags.
+    ///
+    /// The range must not cross the non-canonical hole.
+    #[allow(unused)]
+    pub fn dump_mappings(&self, start: VirtualAddress, end: VirtualAddress) {
+        if start >= end {
+            return;
+        }
+
+        // these change on every access, so they'd split runs for no good reason
+        let ignored_flags = EntryFlags::ACCESSED | EntryFlags::DIRTY;
+
+        let mappings = Page::range_inclusive(
+            Page::containing_address(start),
+            Page::containing_address(end - 1),
+        )
+        .map(|page| {
+            let mapping = self
+                .translate_page_with_flags(page)
+                .map(|(frame, flags)| (frame, flags - ignored_flags));
+            (page, mapping)
+        });
+
+        for run in coalesce_mappings(mappings) {
+            serial_println!(
+                "{:#x}-{:#x} -> {:#x}-{:#x} {:?}",
+                run.page.start_address(),
+                run.page.start_address() + run.count * PAGE_SIZE,
+                run.frame.start_address(),
+                run.frame.start_address() + run.count * PAGE_SIZE,
+                run.flags
+            );
+        }
+    }
+
+    /// Maps `page` to `frame`, failing instead of panicking if `page` is already mapped
+    pub fn try_map_to<A>(
+        &mut self,
+        page: Page,
+        frame: Frame,
+        flags: EntryFlags,
+        allocator: &mut A,
+    ) -> Result<(), MapError>
     where
         A: FrameAllocator,
     {
@@ -81,8 +145,21 @@ impl Mapper {
         let p2 = p3.next_table_create(page.p3_index(), allocator);
         let p1 = p2.next_table_create(page.p2_index(), allocator);
 
-        assert!(p1[page.p1_index()].is_unused());
+        if !p1[page.p1_index()].is_unused() {
+            return Err(MapError::AlreadyMapped);
+        }
         p1[page.p1_index()].set(frame, flags | EntryFlags::PRESENT);
+
+        Ok(())
+    }
+
+    pub fn map_to<A>(&mut self, page: Page, frame: Frame, flags: EntryFlags, allocator: &mut A)
+    where
+        A: FrameAllocator,
+    {
+        let start_address = page.start_address();
+        self.try_map_to(page, frame, flags, allocator)
+            .unwrap_or_else(|err| panic!("failed to map page {:#x}: {:?}", start_address, err));
     }
 
     pub fn map<A>(&mut self, page: Page, flags: EntryFlags, allocator: &mut A)
@@ -101,10 +178,64 @@ impl Mapper {
         self.map_to(page, frame, flags, allocator);
     }
 
+    /// Identity maps `frame` unless it's already identity mapped with at least `flags`.
+    ///
+    /// Useful for regions that may share frames with something mapped earlier. Fails with
+    /// [`MapError::AlreadyMapped`] if the page points to another frame or lacks some of `flags`.
+    pub fn identity_map_if_needed<A>(
+        &mut self,
+        frame: Frame,
+        flags: EntryFlags,
+        allocator: &mut A,
+    ) -> Result<(), MapError>
+    where
+        A: FrameAllocator,
+    {
+        let page = Page::containing_address(frame.start_address());
+        match self.translate_page_with_flags(page) {
+            Some((mapped_frame, mapped_flags))
+                if mapped_frame == frame && mapped_flags.contains(flags | EntryFlags::PRESENT) =>
+            {
+                Ok(())
+            }
+            Some(_) => Err(MapError::AlreadyMapped),
+            None => self.try_map_to(page, frame, flags, allocator),
+        }
+    }
+
+    /// Identity maps every frame in `start..=end`, skipping frames that are already mapped.
+    ///
+    /// Stops at the first frame [`Mapper::identity_map_if_needed`] fails on, frames before it
+    /// stay mapped.
+    pub fn identity_map_range<A>(
+        &mut self,
+        start: Frame,
+        end: Frame,
+        flags: EntryFlags,
+        allocator: &mut A,
+    ) -> Result<(), MapError>
+    where
+        A: FrameAllocator,
+    {
+        let start_address = start.start_address();
+        let pages = (end.number + 1).saturating_sub(start.number);
+        let result = Frame::range_inclusive(start, end)
+            .try_for_each(|frame| self.identity_map_if_needed(frame, flags, allocator));
+        flush_tlb_range(start_address, pages);
+
+        result
+    }
+
     pub fn unmap<A>(&mut self, page: Page, allocator: &mut A)
     where
         A: FrameAllocator,
     {
+        let frame = self.unmap_frame(page);
+        allocator.deallocate_frame(frame);
+    }
+
+    /// Unmaps `page` and returns the frame it pointed to without deallocating it
+    pub fn unmap_frame(&mut self, page: Page) -> Frame {
         assert!(self.translate(page.start_address()).is_some());
 
         let p1 = self
@@ -116,8 +247,51 @@ impl Mapper {
 
         let frame = p1[page.p1_index()].pointed_frame().unwrap();
         p1[page.p1_index()].set_unused();
-        x86_64::instructions::tlb::flush(VirtAddr::new(page.start_address() as u64));
+        flush_tlb(page.start_address());
 
-        allocator.deallocate_frame(frame);
+        frame
     }
 }
+
+/// Consecutive pages mapped to consecutive frames with the same flags
+#[derive(Debug, PartialEq, Eq)]
+pub struct MappingRun {
+    pub page: Page,
+    pub frame: Frame,
+    pub count: usize,
+    pub flags: EntryFlags,
+}
+
+/// Merges `(page, mapping)` pairs into runs. A run ends at an unmapped or non-consecutive
+/// page, a frame that doesn't follow the previous one, or different flags.
+pub fn coalesce_mappings<I>(mappings: I) -> Vec<MappingRun>
+where
+    I: IntoIterator<Item = (Page, Option<(Frame, EntryFlags)>)>,
+{
+    let mut runs: Vec<MappingRun> = vec![];
+
+    for (page, mapping) in mappings {
+        let Some((frame, flags)) = mapping else {
+            continue;
+        };
+
+        if let Some(run) = runs.last_mut() {
+            if run.page + run.count == page
+                && run.frame.number + run.count == frame.number
+                && run.flags == flags
+            {
+                run.count += 1;
+                continue;
+            }
+        }
+
+        runs.push(MappingRun {
+            page,
+            frame,
+            count: 1,
+            flags,
+        });
+    }
+
+    runs
+}
@@ -4,7 +4,10 @@ use crate::arch::x86_64::mm::paging::temporary_page::TemporaryPage;
 use crate::arch::x86_64::mm::FrameAllocator;
 use crate::arch::x86_64::mm::{Frame, PAGE_SIZE};
 
-use crate::kutils::multiboot::get_multiboot_info_start_end;
+use crate::arch::x86_64::utils::flush_tlb_all;
+use crate::kutils::align::is_aligned;
+use crate::kutils::multiboot::{get_initrd_start_end, get_multiboot_info_start_end};
+use core::fmt::{Debug, Formatter};
 use core::ops::{Add, Deref, DerefMut};
 pub use entry::*;
 use multiboot2::BootInformation;
@@ -13,27 +16,37 @@ use x86_64::structures::paging::PhysFrame;
 use x86_64::PhysAddr;
 
 pub mod entry;
-mod mapper;
+pub mod mapper;
 mod table;
-mod temporary_page;
+pub mod temporary_page;
 
 const ENTRY_COUNT: usize = 512;
 
 pub type PhysicalAddress = usize;
 pub type VirtualAddress = usize;
 
-#[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq)]
+/// Whether `address` has bits 48-63 all equal to bit 47
+pub fn is_canonical(address: VirtualAddress) -> bool {
+    !(0x0000_8000_0000_0000..0xffff_8000_0000_0000).contains(&address)
+}
+
+/// Page used for temporarily mapping arbitrary frames
+pub const TEMPORARY_PAGE_ADDRESS: VirtualAddress = 0xdeadbeef * PAGE_SIZE;
+
+#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq)]
 pub struct Page {
     number: usize,
 }
 
+impl Debug for Page {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Page(virt={:#x})", self.start_address())
+    }
+}
+
 impl Page {
     pub fn containing_address(address: VirtualAddress) -> Page {
-        assert!(
-            !(0x0000_8000_0000_0000..0xffff_8000_0000_0000).contains(&address),
-            "invalid address: 0x{:x}",
-            address
-        );
+        kassert!(is_canonical(address), "invalid address: 0x{:x}", address);
 
         Page {
             number: address / PAGE_SIZE,
@@ -44,17 +57,29 @@ impl Page {
         self.number * PAGE_SIZE
     }
 
-    fn p4_index(&self) -> usize {
-        (self.number >> 27) & 0o777
+    pub fn p4_index(&self) -> usize {
+        self.table_index(27)
     }
     fn p3_index(&self) -> usize {
-        (self.number >> 18) & 0o777
+        self.table_index(18)
     }
     fn p2_index(&self) -> usize {
-        (self.number >> 9) & 0o777
+        self.table_index(9)
     }
     fn p1_index(&self) -> usize {
-        self.number & 0o777
+        self.table_index(0)
+    }
+
+    /// Index into the table that `shift` selects. A page pushed out of the canonical range
+    /// (e.g. by `Add`) would otherwise silently wrap into some other table's entries.
+    fn table_index(&self, shift: usize) -> usize {
+        debug_assert!(
+            is_canonical(self.start_address()),
+            "page number {:#x} is outside the canonical address space",
+            self.number
+        );
+
+        (self.number >> shift) & 0o777
     }
 
     pub fn range_inclusive(start: Page, end: Page) -> PageIter {
@@ -133,12 +158,12 @@ impl ActivePageTable {
                 table.p4_frame.clone(),
                 EntryFlags::PRESENT | EntryFlags::WRITABLE,
             );
-            x86_64::instructions::tlb::flush_all();
+            flush_tlb_all();
 
             f(self);
 
             p4_table[511].set(backup, EntryFlags::PRESENT | EntryFlags::WRITABLE);
-            x86_64::instructions::tlb::flush_all();
+            flush_tlb_all();
         }
 
         temporary_page.unmap(self);
@@ -183,21 +208,34 @@ impl InactivePageTable {
 
         InactivePageTable { p4_frame: frame }
     }
+
+    pub fn p4_frame(&self) -> Frame {
+        self.p4_frame.clone()
+    }
+}
+
+/// Translates `virtual_address` through the active page table
+pub fn translate_addr(virtual_address: VirtualAddress) -> Option<PhysicalAddress> {
+    // SAFETY: the recursive mapping is always present and we only read through it
+    let mapper = unsafe { Mapper::new() };
+    mapper.translate(virtual_address)
 }
 
-pub fn remap_the_kernel<A>(allocator: &mut A, boot_info: &BootInformation) -> ActivePageTable
+pub fn remap_the_kernel<A>(
+    allocator: &mut A,
+    temporary_page: &mut TemporaryPage,
+    boot_info: &BootInformation,
+) -> ActivePageTable
 where
     A: FrameAllocator,
 {
-    let mut temporary_page = TemporaryPage::new(Page { number: 0xdeadbeef }, allocator);
-
     let mut active_table = unsafe { ActivePageTable::new() };
     let mut new_table = {
         let frame = allocator.allocate_frame().expect("no more frames");
-        InactivePageTable::new(frame, &mut active_table, &mut temporary_page)
+        InactivePageTable::new(frame, &mut active_table, temporary_page)
     };
 
-    active_table.with(&mut new_table, &mut temporary_page, |mapper| {
+    active_table.with(&mut new_table, temporary_page, |mapper| {
         let elf_sections_tag = boot_info
             .elf_sections_tag()
             .expect("failed to get elf sections tag");
@@ -206,7 +244,7 @@ where
             if !section.is_allocated() {
                 continue;
             }
-            assert_eq!(section.start_address() as usize % PAGE_SIZE, 0);
+            assert!(is_aligned(section.start_address() as usize, PAGE_SIZE));
 
             info!(
                 "mapping section at addr: {:#x}, size: {:#x}",
@@ -223,36 +261,58 @@ where
             }
         }
 
-        let vga_framebuffer_tag = boot_info.framebuffer_tag().unwrap();
-        let vga_framebuffer_end = (vga_framebuffer_tag.address
-            + (vga_framebuffer_tag.height * vga_framebuffer_tag.pitch) as u64)
-            as usize;
-        info!(
-            "{:?}",
-            Frame::range_inclusive(
-                Frame::containing_address(vga_framebuffer_tag.address as usize),
-                Frame::containing_address(vga_framebuffer_end),
-            )
-        );
-        for frame in Frame::range_inclusive(
-            Frame::containing_address(vga_framebuffer_tag.address as usize),
-            Frame::containing_address(vga_framebuffer_end),
-        ) {
-            mapper.identity_map(frame, EntryFlags::WRITABLE, allocator);
+        // headless boots come without a framebuffer, there's nothing to map then
+        if let Some(vga_framebuffer_tag) = boot_info.framebuffer_tag() {
+            let vga_framebuffer_end = (vga_framebuffer_tag.address
+                + (vga_framebuffer_tag.height * vga_framebuffer_tag.pitch) as u64)
+                as usize;
+            info!(
+                "{:?}",
+                Frame::range_inclusive(
+                    Frame::containing_address(vga_framebuffer_tag.address as usize),
+                    Frame::containing_address(vga_framebuffer_end),
+                )
+            );
+            mapper
+                .identity_map_range(
+                    Frame::containing_address(vga_framebuffer_tag.address as usize),
+                    Frame::containing_address(vga_framebuffer_end),
+                    EntryFlags::WRITABLE
+                        | EntryFlags::NO_EXECUTE
+                        | EntryFlags::from_cache_mode(CacheMode::WriteCombining),
+                    allocator,
+                )
+                .expect("framebuffer overlaps an incompatible mapping");
+        } else {
+            warn!("No framebuffer tag, not mapping a framebuffer");
         }
 
         let (multiboot_start, multiboot_end) = get_multiboot_info_start_end(boot_info);
-        for frame in Frame::range_inclusive(
-            Frame::containing_address(multiboot_start),
-            Frame::containing_address(multiboot_end),
-        ) {
-            mapper.identity_map(frame, EntryFlags::PRESENT, allocator);
+        mapper
+            .identity_map_range(
+                Frame::containing_address(multiboot_start),
+                Frame::containing_address(multiboot_end),
+                EntryFlags::PRESENT,
+                allocator,
+            )
+            .expect("multiboot info overlaps an incompatible mapping");
+
+        if let Some((initrd_start, initrd_end)) = get_initrd_start_end(boot_info) {
+            mapper
+                .identity_map_range(
+                    Frame::containing_address(initrd_start),
+                    Frame::containing_address(initrd_end),
+                    EntryFlags::PRESENT,
+                    allocator,
+                )
+                .expect("initrd overlaps an incompatible mapping");
         }
     });
 
     let old_table = active_table.switch(&mut new_table);
     let old_p4_page = Page::containing_address(old_table.p4_frame.start_address());
-    active_table.unmap(old_p4_page, allocator);
+    // the frame is part of the kernel image, it was never handed out so don't free it
+    active_table.unmap_frame(old_p4_page);
     info!("guard page at {:#x}", old_p4_page.start_address());
 
     active_table
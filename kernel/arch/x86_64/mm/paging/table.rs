@@ -71,12 +71,14 @@ impl<L> Index<usize> for Table<L> {
     type Output = Entry;
 
     fn index(&self, index: usize) -> &Self::Output {
+        debug_assert!(index < ENTRY_COUNT, "table index {} out of range", index);
         &self.entries[index]
     }
 }
 
 impl<L> IndexMut<usize> for Table<L> {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        debug_assert!(index < ENTRY_COUNT, "table index {} out of range", index);
         &mut self.entries[index]
     }
 }
@@ -21,7 +21,7 @@ impl TemporaryPage {
     }
 
     pub fn map(&mut self, frame: Frame, active_table: &mut ActivePageTable) -> VirtualAddress {
-        assert!(
+        kassert!(
             active_table.translate_page(self.page).is_none(),
             "temporary page is already mapped"
         );
@@ -30,7 +30,8 @@ impl TemporaryPage {
     }
 
     pub fn unmap(&mut self, active_table: &mut ActivePageTable) {
-        active_table.unmap(self.page, &mut self.allocator);
+        // the frame is owned by whoever asked for it to be mapped, so don't free it
+        active_table.unmap_frame(self.page);
     }
 
     pub fn map_table_frame(
@@ -0,0 +1,189 @@
+use crate::arch::x86_64::mm::paging::entry::EntryFlags;
+#[cfg(feature = "test-mode")]
+use crate::arch::x86_64::mm::paging::mapper::MapError;
+use crate::arch::x86_64::mm::paging::{translate_addr, Page, TEMPORARY_PAGE_ADDRESS};
+use crate::arch::x86_64::mm::{Frame, FrameAllocator, MemoryController, PAGE_SIZE};
+
+/// Pages mapped by the self test, right after the temporary page
+const SCRATCH_PAGES: usize = 4;
+
+impl MemoryController<'_> {
+    /// Maps a scratch range, writes a pattern through it, reads it back and unmaps it again.
+    /// Then checks that a shared frame is only reused once every reference freed it.
+    pub fn self_test(&mut self) -> bool {
+        match self
+            .run_self_test()
+            .and_then(|()| self.frame_refcount_test())
+        {
+            Ok(()) => {
+                info!("Memory self test passed");
+                true
+            }
+            Err(reason) => {
+                error!("Memory self test failed: {}", reason);
+                false
+            }
+        }
+    }
+
+    /// Shares a frame, frees it twice and checks it's only reused after the second free.
+    /// Then checks that freeing free or kernel frames is rejected.
+    fn frame_refcount_test(&mut self) -> Result<(), &'static str> {
+        let frame = self
+            .frame_allocator
+            .allocate_frame()
+            .ok_or("no frames available")?;
+        let number = frame.number;
+
+        if self.frame_allocator.inc_ref(&frame) != 2 {
+            return Err("sharing a frame didn't count a second reference");
+        }
+
+        self.frame_allocator.deallocate_frame(frame.clone());
+        let other = self
+            .frame_allocator
+            .allocate_frame()
+            .ok_or("no frames available")?;
+        if other.number == number {
+            return Err("frame was reused while it still had a reference");
+        }
+
+        self.frame_allocator.deallocate_frame(frame);
+        let reused = self
+            .frame_allocator
+            .allocate_frame()
+            .ok_or("no frames available")?;
+        let reused_number = reused.number;
+
+        self.frame_allocator.deallocate_frame(reused);
+        self.frame_allocator.deallocate_frame(other);
+        if reused_number != number {
+            return Err("freed frame wasn't reused");
+        }
+
+        // neither a frame that's free already nor one of the kernel image may be freed
+        let allocated = self.frame_allocator.allocated_frames();
+        self.frame_allocator
+            .deallocate_frame(Frame::containing_address(number * PAGE_SIZE));
+        self.frame_allocator
+            .deallocate_frame(Frame::containing_address(
+                Self::self_test as fn(&mut Self) -> bool as usize,
+            ));
+        if self.frame_allocator.allocated_frames() != allocated {
+            return Err("freeing a frame that isn't allocated changed the count");
+        }
+
+        let first = self
+            .frame_allocator
+            .allocate_frame()
+            .ok_or("no frames available")?;
+        let second = self
+            .frame_allocator
+            .allocate_frame()
+            .ok_or("no frames available")?;
+        let handed_out_twice = first.number == second.number;
+        self.frame_allocator.deallocate_frame(first);
+        self.frame_allocator.deallocate_frame(second);
+        if handed_out_twice {
+            return Err("a double free handed the frame out twice");
+        }
+
+        Ok(())
+    }
+
+    /// Maps a scratch page and checks that mapping it again fails without touching the mapping
+    #[cfg(feature = "test-mode")]
+    pub fn remap_test(&mut self) -> Result<(), &'static str> {
+        let page = Page::containing_address(TEMPORARY_PAGE_ADDRESS) + 1;
+        if self.active_table.translate_page(page).is_some() {
+            return Err("scratch page is already mapped");
+        }
+
+        self.active_table
+            .map(page, EntryFlags::WRITABLE, &mut self.frame_allocator);
+        let frame = self.active_table.translate_page(page);
+        let other = self
+            .frame_allocator
+            .allocate_frame()
+            .ok_or("no frames available")?;
+        let result = self.active_table.try_map_to(
+            page,
+            other.clone(),
+            EntryFlags::WRITABLE,
+            &mut self.frame_allocator,
+        );
+        let remapped = self.active_table.translate_page(page) != frame;
+
+        self.frame_allocator.deallocate_frame(other);
+        self.active_table.unmap(page, &mut self.frame_allocator);
+
+        if result != Err(MapError::AlreadyMapped) {
+            return Err("mapping a mapped page didn't fail with AlreadyMapped");
+        }
+        if remapped {
+            return Err("failed mapping changed the existing one");
+        }
+
+        Ok(())
+    }
+
+    fn run_self_test(&mut self) -> Result<(), &'static str> {
+        let start_page = Page::containing_address(TEMPORARY_PAGE_ADDRESS) + 1;
+        let end_page = start_page + (SCRATCH_PAGES - 1);
+        let start = start_page.start_address();
+
+        for page in Page::range_inclusive(start_page, end_page) {
+            if self.active_table.translate_page(page).is_some() {
+                return Err("scratch range is already mapped");
+            }
+            self.active_table
+                .map(page, EntryFlags::WRITABLE, &mut self.frame_allocator);
+        }
+
+        // SAFETY: the whole range was just mapped writable
+        let scratch =
+            unsafe { core::slice::from_raw_parts_mut(start as *mut u8, SCRATCH_PAGES * PAGE_SIZE) };
+        fill_pattern(scratch);
+        let mismatch = find_pattern_mismatch(scratch);
+
+        let translated = Page::range_inclusive(start_page, end_page)
+            .all(|page| translate_addr(page.start_address()).is_some());
+
+        for page in Page::range_inclusive(start_page, end_page) {
+            self.active_table.unmap(page, &mut self.frame_allocator);
+        }
+
+        if !translated {
+            return Err("scratch page has no translation after mapping it");
+        }
+        if mismatch.is_some() {
+            return Err("read back a different pattern than was written");
+        }
+        if Page::range_inclusive(start_page, end_page)
+            .any(|page| translate_addr(page.start_address()).is_some())
+        {
+            return Err("scratch page is still mapped after unmapping it");
+        }
+
+        Ok(())
+    }
+}
+
+/// Byte expected at `offset`. Mixes in the page number so a page aliased to the wrong
+/// frame doesn't read back as correct.
+fn pattern_byte(offset: usize) -> u8 {
+    (offset ^ (offset / PAGE_SIZE).wrapping_mul(0x9e)) as u8
+}
+
+fn fill_pattern(buf: &mut [u8]) {
+    for (offset, byte) in buf.iter_mut().enumerate() {
+        *byte = pattern_byte(offset);
+    }
+}
+
+/// Offset of the first byte that doesn't match [`fill_pattern`]
+fn find_pattern_mismatch(buf: &[u8]) -> Option<usize> {
+    buf.iter()
+        .enumerate()
+        .position(|(offset, &byte)| byte != pattern_byte(offset))
+}
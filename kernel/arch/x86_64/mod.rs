@@ -6,6 +6,7 @@ use multiboot2::BootInformation;
 pub mod cpu;
 pub mod interrupts;
 pub mod mm;
+pub mod power;
 pub mod utils;
 
 pub fn initial_setup_x86_64(boot_info: &BootInformation) -> MemoryController {
@@ -15,6 +16,9 @@ pub fn initial_setup_x86_64(boot_info: &BootInformation) -> MemoryController {
     utils::enable_write_protect_bit();
     info!("Enabled write protection bit");
 
+    utils::init_pat();
+    info!("Initialized PAT");
+
     let mut memory_controller = mm::init(boot_info);
     info!("Initialized memory related stuff and remapped the kernel");
     unsafe {
@@ -23,6 +27,12 @@ pub fn initial_setup_x86_64(boot_info: &BootInformation) -> MemoryController {
     info!("Initialized heap allocator");
     display_heap_stats();
 
+    #[cfg(feature = "mm-self-test")]
+    memory_controller.self_test();
+
+    cpu::percpu::init(0);
+    info!("Initialized per-CPU data for the bootstrap processor");
+
     interrupts::init_interrupts(&mut memory_controller);
     info!("Initialized interrupts");
 
@@ -0,0 +1,72 @@
+//! Rebooting and powering off without ACPI. There's no ACPI table parser yet, so both go
+//! through legacy hardware and emulator specific ports instead of the FADT. The emulator is
+//! identified by its CPUID hypervisor signature before any of its ports get written.
+
+use crate::arch::cpu::{cpuid, Cpu};
+use crate::kutils::errors::ErrorCode;
+use x86_64::instructions::port::{Port, PortReadOnly};
+
+const KBC_STATUS_PORT: u16 = 0x64;
+const KBC_COMMAND_PORT: u16 = 0x64;
+/// Status bit set while the controller hasn't taken the last command yet
+const KBC_INPUT_FULL: u8 = 1 << 1;
+/// Pulses the CPU reset line
+const KBC_RESET_PULSE: u8 = 0xfe;
+/// Gives up waiting for the controller after this many status reads
+const KBC_POLL_LIMIT: usize = 100_000;
+
+/// Hypervisor signatures and the hardcoded ACPI PM1a control port and sleep value that
+/// power them off. KVM is assumed to be QEMU, which is what runs this kernel under KVM.
+const EMULATOR_POWER_OFF: [(&str, u16, u16); 3] = [
+    ("TCGTCGTCGTCG", 0x604, 0x2000),
+    ("KVMKVMKVM\0\0\0", 0x604, 0x2000),
+    ("VBoxVBoxVBox", 0x4004, 0x3400),
+];
+
+/// Resets the machine through the 8042 keyboard controller.
+/// Halts if the controller doesn't reset it.
+pub fn reboot() -> ! {
+    Cpu::disable_interrupts();
+
+    unsafe {
+        let mut status = PortReadOnly::<u8>::new(KBC_STATUS_PORT);
+        for _ in 0..KBC_POLL_LIMIT {
+            if status.read() & KBC_INPUT_FULL == 0 {
+                break;
+            }
+        }
+        Port::<u8>::new(KBC_COMMAND_PORT).write(KBC_RESET_PULSE);
+    }
+
+    error!("Reset through the keyboard controller failed, halting");
+    Cpu::halt();
+}
+
+/// Powers off emulators through their ACPI ports.
+/// Returns `ENOSYS` on anything else, real hardware needs the FADT.
+pub fn shutdown() -> Result<(), ErrorCode> {
+    shutdown_with(
+        cpuid::hypervisor_signature().as_deref(),
+        |port, value| unsafe {
+            Port::<u16>::new(port).write(value);
+        },
+    )
+}
+
+/// Writes the power off value for the emulator with hypervisor `signature` through
+/// `write_port`. Nothing gets written for an unknown emulator or bare metal, the port could
+/// belong to any device there.
+pub fn shutdown_with(
+    signature: Option<&str>,
+    mut write_port: impl FnMut(u16, u16),
+) -> Result<(), ErrorCode> {
+    let power_off = EMULATOR_POWER_OFF
+        .iter()
+        .find(|(emulator, _, _)| Some(*emulator) == signature);
+    if let Some(&(_, port, value)) = power_off {
+        write_port(port, value);
+    }
+
+    // still running, the write didn't power off
+    Err(ErrorCode::ENOSYS)
+}
@@ -1,25 +1,68 @@
-use core::arch::asm;
-use x86_64::registers::model_specific::Msr;
+use crate::arch::x86_64::mm::paging::VirtualAddress;
+use crate::arch::x86_64::mm::PAGE_SIZE;
+use x86_64::registers::control::{Cr0, Cr0Flags};
+use x86_64::registers::model_specific::{Efer, EferFlags, Msr};
+use x86_64::VirtAddr;
 
-pub fn enable_nxe_bit() {
-    let nxe_bit = 1 << 11;
-    let mut msr = Msr::new(3221225600);
+const IA32_PAT: u32 = 0x277;
 
+pub fn enable_nxe_bit() {
     unsafe {
-        let efer = msr.read();
-        msr.write(efer | nxe_bit);
+        Efer::update(|flags| flags.insert(EferFlags::NO_EXECUTE_ENABLE));
     }
 }
 
 pub fn enable_write_protect_bit() {
-    let write_protect_bit = 1 << 16;
-    let value: u64;
-
     unsafe {
-        asm!("mov {}, cr0", out(reg) value, options(nomem, nostack, preserves_flags));
+        Cr0::update(|flags| flags.insert(Cr0Flags::WRITE_PROTECT));
+    }
+}
+
+/// Invalidates the TLB entry of the page containing `address`
+pub fn flush_tlb(address: VirtualAddress) {
+    x86_64::instructions::tlb::flush(VirtAddr::new(address as u64));
+}
+
+/// Past this many pages, reloading CR3 is cheaper than invalidating pages one by one
+const FLUSH_TLB_RANGE_THRESHOLD: usize = 32;
+
+/// Whether flushing `pages` pages should reload CR3 rather than invalidate them one by one
+pub fn flush_range_reloads_cr3(pages: usize) -> bool {
+    pages > FLUSH_TLB_RANGE_THRESHOLD
+}
+
+/// Invalidates the TLB entries of `pages` pages starting at `start`
+pub fn flush_tlb_range(start: VirtualAddress, pages: usize) {
+    if flush_range_reloads_cr3(pages) {
+        flush_tlb_all();
+        return;
     }
 
+    for page in 0..pages {
+        flush_tlb(start + page * PAGE_SIZE);
+    }
+}
+
+/// Invalidates all non-global TLB entries by reloading CR3
+pub fn flush_tlb_all() {
+    x86_64::instructions::tlb::flush_all();
+}
+
+/// Programs the PAT so that PAT index 1 (PWT set, PCD clear) selects write-combining.
+///
+/// Resulting layout for PAT indices 0-3: write-back, write-combining, uncached-minus,
+/// uncacheable. Indices 4-7 are unused since we never set the PAT bit in entries.
+pub fn init_pat() {
+    const WRITE_BACK: u64 = 0x06;
+    const WRITE_COMBINING: u64 = 0x01;
+    const UNCACHED_MINUS: u64 = 0x07;
+    const UNCACHEABLE: u64 = 0x00;
+
+    let low = WRITE_BACK | WRITE_COMBINING << 8 | UNCACHED_MINUS << 16 | UNCACHEABLE << 24;
+    let pat = low | low << 32;
+
+    let mut msr = Msr::new(IA32_PAT);
     unsafe {
-        asm!("mov cr0, {}", in(reg) value | write_protect_bit, options(nostack, preserves_flags));
+        msr.write(pat);
     }
 }
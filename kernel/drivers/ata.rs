@@ -0,0 +1,270 @@
+use crate::arch::cpu::Cpu;
+use crate::fs::block::BlockDevice;
+use crate::kutils::errors::ErrorCode;
+use x86_64::instructions::port::Port;
+
+const PRIMARY_IO_BASE: u16 = 0x1f0;
+const PRIMARY_CONTROL: u16 = 0x3f6;
+
+pub const SECTOR_SIZE: usize = 512;
+
+const STATUS_ERR: u8 = 1 << 0;
+const STATUS_DRQ: u8 = 1 << 3;
+const STATUS_DF: u8 = 1 << 5;
+const STATUS_BSY: u8 = 1 << 7;
+
+const COMMAND_READ_SECTORS: u8 = 0x20;
+const COMMAND_WRITE_SECTORS: u8 = 0x30;
+const COMMAND_CACHE_FLUSH: u8 = 0xe7;
+const COMMAND_IDENTIFY: u8 = 0xec;
+
+/// Device control bit that stops the drive from raising IRQ14, we poll instead
+const CONTROL_NIEN: u8 = 1 << 1;
+
+/// Status reads before giving up on the drive
+const POLL_LIMIT: usize = 1_000_000;
+
+/// Highest sector 28-bit LBA can address
+const MAX_LBA28: u64 = (1 << 28) - 1;
+
+#[allow(unused)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AtaDrive {
+    Master,
+    Slave,
+}
+
+/// A drive on the primary ATA bus, driven in PIO mode
+pub struct AtaDevice {
+    drive: AtaDrive,
+    sector_count: u64,
+    data: Port<u16>,
+    error: Port<u8>,
+    sector_count_port: Port<u8>,
+    lba_low: Port<u8>,
+    lba_mid: Port<u8>,
+    lba_high: Port<u8>,
+    drive_select: Port<u8>,
+    command: Port<u8>,
+    control: Port<u8>,
+}
+
+#[allow(unused)]
+impl AtaDevice {
+    /// Identifies `drive` on the primary bus. Fails with `ENODEV` if there's no ATA drive.
+    pub fn new(drive: AtaDrive) -> Result<Self, ErrorCode> {
+        let mut device = Self {
+            drive,
+            sector_count: 0,
+            data: Port::new(PRIMARY_IO_BASE),
+            error: Port::new(PRIMARY_IO_BASE + 1),
+            sector_count_port: Port::new(PRIMARY_IO_BASE + 2),
+            lba_low: Port::new(PRIMARY_IO_BASE + 3),
+            lba_mid: Port::new(PRIMARY_IO_BASE + 4),
+            lba_high: Port::new(PRIMARY_IO_BASE + 5),
+            drive_select: Port::new(PRIMARY_IO_BASE + 6),
+            command: Port::new(PRIMARY_IO_BASE + 7),
+            control: Port::new(PRIMARY_CONTROL),
+        };
+
+        device.identify()?;
+        Ok(device)
+    }
+
+    pub fn drive(&self) -> AtaDrive {
+        self.drive
+    }
+
+    fn identify(&mut self) -> Result<(), ErrorCode> {
+        unsafe {
+            self.control.write(CONTROL_NIEN);
+            self.drive_select
+                .write(drive_select_byte(self.drive, 0, false));
+            self.select_delay();
+
+            self.sector_count_port.write(0);
+            self.lba_low.write(0);
+            self.lba_mid.write(0);
+            self.lba_high.write(0);
+            self.command.write(COMMAND_IDENTIFY);
+
+            // an empty slot reads back as 0, a bus without drives floats to 0xff
+            let status = self.status();
+            if status == 0 || status == 0xff {
+                return Err(ErrorCode::ENODEV);
+            }
+            self.wait_not_busy()?;
+
+            // ATAPI and SATA drives set these, they don't take ATA commands
+            if self.lba_mid.read() != 0 || self.lba_high.read() != 0 {
+                return Err(ErrorCode::ENODEV);
+            }
+        }
+        self.wait_data_request()?;
+
+        let mut identify = [0u16; 256];
+        for word in identify.iter_mut() {
+            *word = unsafe { self.data.read() };
+        }
+
+        // words 60-61 hold the number of sectors addressable with 28-bit LBA
+        self.sector_count = (identify[61] as u64) << 16 | identify[60] as u64;
+        if self.sector_count == 0 {
+            return Err(ErrorCode::ENODEV);
+        }
+
+        Ok(())
+    }
+
+    /// Sets up the task file registers for a single sector transfer and issues `command`
+    fn issue(&mut self, lba: u64, command: u8) -> Result<(), ErrorCode> {
+        if lba >= self.sector_count || lba > MAX_LBA28 {
+            return Err(ErrorCode::EINVAL);
+        }
+
+        unsafe {
+            self.drive_select
+                .write(drive_select_byte(self.drive, lba, true));
+            self.select_delay();
+        }
+        self.wait_not_busy()?;
+
+        let [low, mid, high, _] = lba28_bytes(lba);
+        unsafe {
+            self.sector_count_port.write(1);
+            self.lba_low.write(low);
+            self.lba_mid.write(mid);
+            self.lba_high.write(high);
+            self.command.write(command);
+        }
+
+        Ok(())
+    }
+
+    fn status(&mut self) -> u8 {
+        unsafe { self.command.read() }
+    }
+
+    /// The drive needs ~400ns to put its status on the bus after being selected.
+    /// Each alternate status read takes about 100ns.
+    fn select_delay(&mut self) {
+        for _ in 0..4 {
+            unsafe {
+                self.control.read();
+            }
+        }
+    }
+
+    fn wait_not_busy(&mut self) -> Result<u8, ErrorCode> {
+        self.poll(|status| !is_busy(status))
+    }
+
+    fn wait_data_request(&mut self) -> Result<u8, ErrorCode> {
+        let status = self.poll(|status| is_ready_for_data(status) || has_failed(status))?;
+        if has_failed(status) {
+            let error = unsafe { self.error.read() };
+            warn!(
+                "ATA {:?} drive failed, error register: {:#x}",
+                self.drive, error
+            );
+            return Err(ErrorCode::EIO);
+        }
+
+        Ok(status)
+    }
+
+    fn poll(&mut self, done: impl Fn(u8) -> bool) -> Result<u8, ErrorCode> {
+        for _ in 0..POLL_LIMIT {
+            let status = self.status();
+            if done(status) {
+                return Ok(status);
+            }
+
+            Cpu::spin_loop_hint();
+        }
+
+        Err(ErrorCode::ETIMEDOUT)
+    }
+}
+
+impl BlockDevice for AtaDevice {
+    fn read_block(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), ErrorCode> {
+        if buf.len() != SECTOR_SIZE {
+            return Err(ErrorCode::EINVAL);
+        }
+
+        self.issue(lba, COMMAND_READ_SECTORS)?;
+        self.wait_data_request()?;
+        for bytes in buf.chunks_exact_mut(2) {
+            let word = unsafe { self.data.read() };
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    fn write_block(&mut self, lba: u64, buf: &[u8]) -> Result<(), ErrorCode> {
+        if buf.len() != SECTOR_SIZE {
+            return Err(ErrorCode::EINVAL);
+        }
+
+        self.issue(lba, COMMAND_WRITE_SECTORS)?;
+        self.wait_data_request()?;
+        for bytes in buf.chunks_exact(2) {
+            unsafe {
+                self.data.write(u16::from_le_bytes([bytes[0], bytes[1]]));
+            }
+        }
+
+        unsafe {
+            self.command.write(COMMAND_CACHE_FLUSH);
+        }
+        self.wait_not_busy()?;
+
+        Ok(())
+    }
+
+    fn block_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    fn block_count(&self) -> u64 {
+        self.sector_count
+    }
+}
+
+pub fn is_busy(status: u8) -> bool {
+    status & STATUS_BSY != 0
+}
+
+pub fn is_ready_for_data(status: u8) -> bool {
+    !is_busy(status) && status & STATUS_DRQ != 0
+}
+
+pub fn has_failed(status: u8) -> bool {
+    !is_busy(status) && status & (STATUS_ERR | STATUS_DF) != 0
+}
+
+/// Drive/head register value. In LBA mode bits 24-27 of `lba` go in the low nibble.
+pub fn drive_select_byte(drive: AtaDrive, lba: u64, lba_mode: bool) -> u8 {
+    let drive_bit = match drive {
+        AtaDrive::Master => 0,
+        AtaDrive::Slave => 1 << 4,
+    };
+
+    if lba_mode {
+        0xe0 | drive_bit | lba28_bytes(lba)[3]
+    } else {
+        0xa0 | drive_bit
+    }
+}
+
+/// Splits a 28-bit LBA into its low, mid, high and top nibble bytes
+pub fn lba28_bytes(lba: u64) -> [u8; 4] {
+    [
+        lba as u8,
+        (lba >> 8) as u8,
+        (lba >> 16) as u8,
+        ((lba >> 24) & 0x0f) as u8,
+    ]
+}
@@ -1,11 +1,19 @@
 use crate::fs::path::Path;
+use crate::fs::vfs::VFS;
 use crate::kutils::errors::ErrorCode;
 use alloc::sync::Arc;
 use core::fmt::{Display, Formatter};
 use spin::Mutex;
 
+pub mod block;
+pub mod cwd;
+pub mod devfs;
+pub mod fat;
+pub mod fd;
+pub mod initrd;
 pub mod path;
 pub mod ramfs;
+pub mod tar;
 pub mod vfs;
 
 pub struct FSNode {
@@ -44,6 +52,18 @@ pub enum FSNodeType {
     Symlink,
 }
 
+/// What [`Filesystem::metadata`] reports about a node
+#[allow(unused)]
+#[derive(Debug, Copy, Clone)]
+pub struct Metadata {
+    pub size: usize,
+    pub typ: FSNodeType,
+    /// Milliseconds since boot, `None` if the filesystem doesn't keep track of it.
+    /// Becomes wall clock time once there's an RTC driver.
+    pub created: Option<u64>,
+    pub modified: Option<u64>,
+}
+
 pub trait Filesystem: Send + Sync {
     fn root(&self, arc_ref: Arc<Mutex<Box<dyn Filesystem>>>) -> FSNode;
     fn open(&mut self, path: Path, arc_ref: Arc<Mutex<Box<dyn Filesystem>>>) -> IOResult;
@@ -54,6 +74,8 @@ pub trait Filesystem: Send + Sync {
         path: Path,
         arc_ref: Arc<Mutex<Box<dyn Filesystem>>>,
     ) -> Result<Vec<FSNode>, ErrorCode>;
+    /// Writes `bytes` to the inclusive range `start..=end`, returns the number of bytes written.
+    /// Fails with `EINVAL` if the range is backwards or longer than `bytes`.
     fn write(
         &mut self,
         node: &FSNode,
@@ -63,8 +85,84 @@ pub trait Filesystem: Send + Sync {
     ) -> Result<usize, ErrorCode>;
     fn read(&mut self, node: &FSNode, start: usize, end: usize) -> Result<Vec<u8>, ErrorCode>;
     fn fsize(&mut self, path: Path) -> Result<usize, ErrorCode>;
+    /// Cuts the file down or zero-extends it to `size` bytes
+    fn truncate(&mut self, node: &FSNode, size: usize) -> Result<(), ErrorCode>;
+    fn metadata(&mut self, path: Path) -> Result<Metadata, ErrorCode>;
+
+    /// Creates `path` along with any missing parent directories.
+    /// Directories that already exist are left alone.
+    fn create_dir_all(&mut self, path: Path, arc_ref: Arc<Mutex<Box<dyn Filesystem>>>) -> IOResult {
+        for depth in 0..path.segments().len() {
+            let dir_path = path.path_from_range(0, depth);
+            match self.open(dir_path.clone(), arc_ref.clone()) {
+                Ok(node) if node.typ() == FSNodeType::Dir => continue,
+                Ok(_) => return Err(ErrorCode::ENOTDIR),
+                Err(ErrorCode::ENOENT) => {
+                    self.create_dir(dir_path, arc_ref.clone())?;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.open(path, arc_ref)
+    }
+
+    /// Reads up to `buf.len()` bytes starting at `offset`.
+    /// Returns the number of bytes read, which is short near the end of the file.
+    fn read_at(
+        &mut self,
+        node: &FSNode,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, ErrorCode> {
+        let size = self.fsize(node.path.clone())?;
+        if buf.is_empty() || offset >= size {
+            return Ok(0);
+        }
+
+        let end = (offset + buf.len()).min(size) - 1;
+        let bytes = self.read(node, offset, end)?;
+        buf[..bytes.len()].copy_from_slice(&bytes);
+
+        Ok(bytes.len())
+    }
+
+    /// Writes `bytes` starting at `offset`, extending the file if needed
+    fn write_at(&mut self, node: &FSNode, offset: usize, bytes: &[u8]) -> Result<usize, ErrorCode> {
+        if bytes.is_empty() {
+            return Ok(0);
+        }
+
+        self.write(node, bytes.to_vec(), offset, offset + bytes.len() - 1)
+    }
+
+    /// Writes `bytes` at the end of the file, returns the number of bytes written
+    fn append(&mut self, node: &FSNode, bytes: &[u8]) -> Result<usize, ErrorCode> {
+        let size = self.fsize(node.path.clone())?;
+        self.write_at(node, size, bytes)
+    }
+
     fn close(&mut self, fs_node: FSNode);
     fn unmount(&mut self);
 }
 
 type IOResult = Result<FSNode, ErrorCode>;
+
+/// Visits every node below `root` depth first, parents before their children.
+///
+/// Symlinks are passed to `visitor` but never followed, so links can't make it loop.
+/// [`vfs::VFS`] is only locked while listing a directory, so `visitor` can use it.
+#[allow(unused)]
+pub fn walk(root: Path, visitor: &mut dyn FnMut(&FSNode)) -> Result<(), ErrorCode> {
+    let children = VFS.lock().list_path(root.clone())?;
+    for node in children {
+        visitor(&node);
+
+        if node.typ() == FSNodeType::Dir {
+            // node paths are relative to their mountpoint, build the full path from ours
+            walk(root.append(node.name()), visitor)?;
+        }
+    }
+
+    Ok(())
+}
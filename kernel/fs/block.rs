@@ -0,0 +1,85 @@
+use crate::kutils::errors::ErrorCode;
+
+/// Storage addressed in fixed-size blocks
+#[allow(unused)]
+pub trait BlockDevice: Send + Sync {
+    /// Reads the block at `lba` into `buf`, which must be exactly one block long
+    fn read_block(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), ErrorCode>;
+    /// Writes `buf`, which must be exactly one block long, to the block at `lba`
+    fn write_block(&mut self, lba: u64, buf: &[u8]) -> Result<(), ErrorCode>;
+    fn block_size(&self) -> usize;
+    fn block_count(&self) -> u64;
+}
+
+/// Block device backed by memory, e.g. a disk image loaded as a module
+pub struct MemBlockDevice {
+    data: Vec<u8>,
+    block_size: usize,
+}
+
+#[allow(unused)]
+impl MemBlockDevice {
+    /// Wraps `data`, whose length must be a multiple of `block_size`
+    pub fn new(data: Vec<u8>, block_size: usize) -> Result<Self, ErrorCode> {
+        if block_size == 0 || data.len() % block_size != 0 {
+            return Err(ErrorCode::EINVAL);
+        }
+
+        Ok(Self { data, block_size })
+    }
+
+    fn block_range(&self, lba: u64, len: usize) -> Result<core::ops::Range<usize>, ErrorCode> {
+        if len != self.block_size {
+            return Err(ErrorCode::EINVAL);
+        }
+        if lba >= self.block_count() {
+            return Err(ErrorCode::EIO);
+        }
+
+        let start = lba as usize * self.block_size;
+        Ok(start..start + self.block_size)
+    }
+}
+
+impl BlockDevice for MemBlockDevice {
+    fn read_block(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), ErrorCode> {
+        let range = self.block_range(lba, buf.len())?;
+        buf.copy_from_slice(&self.data[range]);
+
+        Ok(())
+    }
+
+    fn write_block(&mut self, lba: u64, buf: &[u8]) -> Result<(), ErrorCode> {
+        let range = self.block_range(lba, buf.len())?;
+        self.data[range].copy_from_slice(buf);
+
+        Ok(())
+    }
+
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn block_count(&self) -> u64 {
+        (self.data.len() / self.block_size) as u64
+    }
+}
+
+/// Reads `len` bytes starting at byte `offset` from whichever blocks hold them
+pub fn read_bytes(
+    device: &mut dyn BlockDevice,
+    offset: usize,
+    len: usize,
+) -> Result<Vec<u8>, ErrorCode> {
+    let block_size = device.block_size();
+    let first_block = offset / block_size;
+    let last_block = (offset + len + block_size - 1) / block_size;
+
+    let mut bytes = vec![0; (last_block - first_block) * block_size];
+    for (idx, block) in bytes.chunks_exact_mut(block_size).enumerate() {
+        device.read_block((first_block + idx) as u64, block)?;
+    }
+
+    let start = offset - first_block * block_size;
+    Ok(bytes[start..start + len].to_vec())
+}
@@ -0,0 +1,39 @@
+use crate::fs::path::Path;
+use crate::fs::vfs::VFS;
+use crate::fs::FSNodeType;
+use crate::kutils::errors::ErrorCode;
+
+/// Current working directory, relative paths are resolved against it
+pub struct Cwd {
+    path: Path,
+}
+
+#[allow(unused)]
+impl Cwd {
+    /// Starts out at the root
+    pub fn new() -> Self {
+        Self {
+            path: Path::new("/"),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the absolute path `input` refers to
+    pub fn resolve(&self, input: &str) -> Path {
+        self.path.join(&Path::new(input))
+    }
+
+    /// Changes to `input`, which has to be an existing directory
+    pub fn chdir(&mut self, input: &str) -> Result<(), ErrorCode> {
+        let path = self.resolve(input);
+        if VFS.lock().open(path.clone())?.typ() != FSNodeType::Dir {
+            return Err(ErrorCode::ENOTDIR);
+        }
+
+        self.path = path;
+        Ok(())
+    }
+}
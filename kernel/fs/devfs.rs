@@ -0,0 +1,198 @@
+use crate::fs::path::Path;
+use crate::fs::{FSNode, FSNodeType, Filesystem, IOResult, Metadata};
+use crate::kutils::errors::ErrorCode;
+use crate::kutils::possibly_uninit::PossiblyUninit;
+use crate::vga::VGA_DRAWER;
+use alloc::sync::Arc;
+use spin::Mutex;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Device {
+    /// Writes go to the kernel log output(serial and screen)
+    Console,
+    /// Raw framebuffer bytes
+    Framebuffer,
+    /// Discards writes, always at the end of file
+    Null,
+}
+
+const DEVICES: [(&str, Device); 3] = [
+    ("console", Device::Console),
+    ("fb", Device::Framebuffer),
+    ("null", Device::Null),
+];
+
+/// Filesystem whose files are backed by devices instead of stored bytes
+pub struct DevFs;
+
+impl DevFs {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Returns the device at `path`, `None` for the root directory
+    fn lookup(path: &Path) -> Result<Option<Device>, ErrorCode> {
+        match path.segments() {
+            [] => Ok(None),
+            [name] => DEVICES
+                .iter()
+                .find(|(device_name, _)| device_name == name)
+                .map(|(_, device)| Some(*device))
+                .ok_or(ErrorCode::ENOENT),
+            _ => Err(ErrorCode::ENOENT),
+        }
+    }
+}
+
+impl Filesystem for DevFs {
+    fn root(&self, arc_ref: Arc<Mutex<Box<dyn Filesystem>>>) -> FSNode {
+        FSNode {
+            name: String::from("/"),
+            path: Path::new("/"),
+            typ: FSNodeType::Dir,
+            fs: arc_ref.clone(),
+        }
+    }
+
+    fn open(&mut self, path: Path, arc_ref: Arc<Mutex<Box<dyn Filesystem>>>) -> IOResult {
+        match Self::lookup(&path)? {
+            None => Ok(self.root(arc_ref)),
+            Some(_) => Ok(FSNode {
+                name: path.segments()[0].clone(),
+                typ: FSNodeType::File,
+                fs: arc_ref,
+                path,
+            }),
+        }
+    }
+
+    fn create_file(&mut self, _: Path, _: Arc<Mutex<Box<dyn Filesystem>>>) -> IOResult {
+        Err(ErrorCode::EPERM)
+    }
+
+    fn create_dir(&mut self, _: Path, _: Arc<Mutex<Box<dyn Filesystem>>>) -> IOResult {
+        Err(ErrorCode::EPERM)
+    }
+
+    fn list_path(
+        &mut self,
+        path: Path,
+        arc_ref: Arc<Mutex<Box<dyn Filesystem>>>,
+    ) -> Result<Vec<FSNode>, ErrorCode> {
+        if Self::lookup(&path)?.is_some() {
+            return Err(ErrorCode::ENOTDIR);
+        }
+
+        Ok(DEVICES
+            .iter()
+            .map(|(name, _)| FSNode {
+                name: String::from(*name),
+                path: path.append(name),
+                typ: FSNodeType::File,
+                fs: arc_ref.clone(),
+            })
+            .collect())
+    }
+
+    fn write(
+        &mut self,
+        node: &FSNode,
+        bytes: Vec<u8>,
+        start: usize,
+        end: usize,
+    ) -> Result<usize, ErrorCode> {
+        let Some(device) = Self::lookup(&node.path)? else {
+            return Err(ErrorCode::EISDIR);
+        };
+        let bytes = &bytes[..(end + 1 - start).min(bytes.len())];
+
+        match device {
+            Device::Console => {
+                print_raw!("{}", String::from_utf8_lossy(bytes));
+                Ok(bytes.len())
+            }
+            Device::Framebuffer => {
+                let mut drawer = VGA_DRAWER.lock();
+                let PossiblyUninit::Init(drawer) = &mut *drawer else {
+                    return Err(ErrorCode::ENXIO);
+                };
+
+                let framebuffer = drawer.buffer.bytes_mut();
+                if start >= framebuffer.len() {
+                    return Err(ErrorCode::ENOSPC);
+                }
+                let written = bytes.len().min(framebuffer.len() - start);
+                framebuffer[start..start + written].copy_from_slice(&bytes[..written]);
+                drawer.buffer.commit();
+
+                Ok(written)
+            }
+            Device::Null => Ok(bytes.len()),
+        }
+    }
+
+    fn read(&mut self, node: &FSNode, start: usize, end: usize) -> Result<Vec<u8>, ErrorCode> {
+        let Some(device) = Self::lookup(&node.path)? else {
+            return Err(ErrorCode::EISDIR);
+        };
+
+        match device {
+            Device::Framebuffer => {
+                let drawer = VGA_DRAWER.lock();
+                let PossiblyUninit::Init(drawer) = &*drawer else {
+                    return Err(ErrorCode::ENXIO);
+                };
+
+                drawer
+                    .buffer
+                    .bytes()
+                    .get(start..end + 1)
+                    .map(|bytes| bytes.to_vec())
+                    .ok_or(ErrorCode::EINVAL)
+            }
+            // nothing to read
+            Device::Console | Device::Null => Ok(vec![]),
+        }
+    }
+
+    fn fsize(&mut self, path: Path) -> Result<usize, ErrorCode> {
+        match Self::lookup(&path)? {
+            None => Err(ErrorCode::EISDIR),
+            Some(Device::Framebuffer) => match &*VGA_DRAWER.lock() {
+                PossiblyUninit::Init(drawer) => Ok(drawer.buffer.size()),
+                PossiblyUninit::Uninit => Ok(0),
+            },
+            Some(Device::Console | Device::Null) => Ok(0),
+        }
+    }
+
+    fn truncate(&mut self, node: &FSNode, _: usize) -> Result<(), ErrorCode> {
+        match Self::lookup(&node.path)? {
+            None => Err(ErrorCode::EISDIR),
+            // devices don't store anything to cut off
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn metadata(&mut self, path: Path) -> Result<Metadata, ErrorCode> {
+        let (typ, size) = match Self::lookup(&path)? {
+            None => (FSNodeType::Dir, 0),
+            Some(_) => (FSNodeType::File, self.fsize(path)?),
+        };
+
+        Ok(Metadata {
+            size,
+            typ,
+            created: None,
+            modified: None,
+        })
+    }
+
+    fn close(&mut self, _: FSNode) {
+        // do nothing
+    }
+
+    fn unmount(&mut self) {
+        // do nothing
+    }
+}
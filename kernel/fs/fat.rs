@@ -0,0 +1,477 @@
+use crate::fs::block::{read_bytes, BlockDevice};
+use crate::fs::path::Path;
+use crate::fs::{FSNode, FSNodeType, Filesystem, IOResult, Metadata};
+use crate::kutils::errors::ErrorCode;
+use alloc::sync::Arc;
+use spin::Mutex;
+
+const DIR_ENTRY_SIZE: usize = 32;
+
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_LONG_NAME: u8 = 0x0f;
+
+/// First byte of a directory entry that marks the end of the directory
+const ENTRY_END: u8 = 0x00;
+/// First byte of a deleted directory entry
+const ENTRY_DELETED: u8 = 0xe5;
+
+/// Read-only FAT16/FAT32 filesystem on a block device
+pub struct FatFS {
+    device: Box<dyn BlockDevice>,
+    bpb: BiosParameterBlock,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum FatType {
+    Fat16,
+    Fat32,
+}
+
+/// The parts of the BIOS parameter block we need, with the derived layout
+#[derive(Debug)]
+struct BiosParameterBlock {
+    fat_type: FatType,
+    bytes_per_sector: usize,
+    sectors_per_cluster: usize,
+    /// First sector of the first FAT
+    fat_start: usize,
+    /// First sector of the fixed root directory, FAT16 only
+    root_dir_start: usize,
+    root_dir_sectors: usize,
+    /// First sector of cluster 2
+    data_start: usize,
+    cluster_count: usize,
+    /// First cluster of the root directory, FAT32 only
+    root_cluster: u32,
+}
+
+impl BiosParameterBlock {
+    fn parse(boot_sector: &[u8]) -> Result<Self, ErrorCode> {
+        if boot_sector.len() < 512 || boot_sector[510..512] != [0x55, 0xaa] {
+            return Err(ErrorCode::EINVAL);
+        }
+
+        let bytes_per_sector = read_u16(boot_sector, 11) as usize;
+        let sectors_per_cluster = boot_sector[13] as usize;
+        let reserved_sectors = read_u16(boot_sector, 14) as usize;
+        let fat_count = boot_sector[16] as usize;
+        let root_entry_count = read_u16(boot_sector, 17) as usize;
+
+        let total_sectors = match read_u16(boot_sector, 19) {
+            0 => read_u32(boot_sector, 32) as usize,
+            count => count as usize,
+        };
+        let sectors_per_fat = match read_u16(boot_sector, 22) {
+            0 => read_u32(boot_sector, 36) as usize,
+            count => count as usize,
+        };
+
+        if !bytes_per_sector.is_power_of_two()
+            || bytes_per_sector < 512
+            || !sectors_per_cluster.is_power_of_two()
+            || fat_count == 0
+        {
+            return Err(ErrorCode::EINVAL);
+        }
+
+        let root_dir_sectors =
+            (root_entry_count * DIR_ENTRY_SIZE + bytes_per_sector - 1) / bytes_per_sector;
+        let root_dir_start = reserved_sectors + fat_count * sectors_per_fat;
+        let data_start = root_dir_start + root_dir_sectors;
+        let cluster_count = total_sectors
+            .checked_sub(data_start)
+            .ok_or(ErrorCode::EINVAL)?
+            / sectors_per_cluster;
+
+        // the cluster count alone decides the FAT type
+        let fat_type = if cluster_count < 4085 {
+            // FAT12 isn't supported
+            return Err(ErrorCode::EINVAL);
+        } else if cluster_count < 65525 {
+            FatType::Fat16
+        } else {
+            FatType::Fat32
+        };
+
+        Ok(Self {
+            fat_type,
+            bytes_per_sector,
+            sectors_per_cluster,
+            fat_start: reserved_sectors,
+            root_dir_start,
+            root_dir_sectors,
+            data_start,
+            cluster_count,
+            root_cluster: read_u32(boot_sector, 44),
+        })
+    }
+
+    fn cluster_size(&self) -> usize {
+        self.bytes_per_sector * self.sectors_per_cluster
+    }
+}
+
+/// A directory entry with its long name already assembled
+#[derive(Debug, Clone)]
+struct FatDirEntry {
+    name: String,
+    typ: FSNodeType,
+    first_cluster: u32,
+    size: usize,
+}
+
+#[allow(unused)]
+impl FatFS {
+    pub fn new(mut device: Box<dyn BlockDevice>) -> Result<Self, ErrorCode> {
+        let boot_sector = read_bytes(device.as_mut(), 0, 512)?;
+        let bpb = BiosParameterBlock::parse(&boot_sector)?;
+
+        Ok(Self { device, bpb })
+    }
+
+    fn read_sectors(&mut self, sector: usize, count: usize) -> Result<Vec<u8>, ErrorCode> {
+        read_bytes(
+            self.device.as_mut(),
+            sector * self.bpb.bytes_per_sector,
+            count * self.bpb.bytes_per_sector,
+        )
+    }
+
+    fn read_cluster(&mut self, cluster: u32) -> Result<Vec<u8>, ErrorCode> {
+        // data clusters are numbered from 2
+        if cluster < 2 {
+            return Err(ErrorCode::EIO);
+        }
+
+        let sector = self.bpb.data_start + (cluster as usize - 2) * self.bpb.sectors_per_cluster;
+        self.read_sectors(sector, self.bpb.sectors_per_cluster)
+    }
+
+    /// Looks up the cluster following `cluster` in the FAT, `None` at the end of the chain
+    fn next_cluster(&mut self, cluster: u32) -> Result<Option<u32>, ErrorCode> {
+        let (offset, entry_size) = match self.bpb.fat_type {
+            FatType::Fat16 => (cluster as usize * 2, 2),
+            FatType::Fat32 => (cluster as usize * 4, 4),
+        };
+        let sector = self.bpb.fat_start + offset / self.bpb.bytes_per_sector;
+        let offset = offset % self.bpb.bytes_per_sector;
+        let fat_sector = self.read_sectors(sector, 1)?;
+
+        let (next, end_of_chain, bad) = match entry_size {
+            2 => (read_u16(&fat_sector, offset) as u32, 0xfff8, 0xfff7),
+            _ => (
+                read_u32(&fat_sector, offset) & 0x0fff_ffff,
+                0x0fff_fff8,
+                0x0fff_fff7,
+            ),
+        };
+
+        if next >= end_of_chain {
+            Ok(None)
+        } else if next == bad || next < 2 || next as usize >= self.bpb.cluster_count + 2 {
+            Err(ErrorCode::EIO)
+        } else {
+            Ok(Some(next))
+        }
+    }
+
+    /// Collects the cluster chain starting at `first_cluster`
+    fn cluster_chain(&mut self, first_cluster: u32) -> Result<Vec<u32>, ErrorCode> {
+        let mut chain = vec![];
+        let mut cluster = Some(first_cluster);
+
+        while let Some(current) = cluster {
+            // a chain longer than the volume means the FAT has a loop
+            if chain.len() > self.bpb.cluster_count {
+                return Err(ErrorCode::EIO);
+            }
+
+            chain.push(current);
+            cluster = self.next_cluster(current)?;
+        }
+
+        Ok(chain)
+    }
+
+    fn root_entry(&self) -> FatDirEntry {
+        FatDirEntry {
+            name: String::from("/"),
+            typ: FSNodeType::Dir,
+            first_cluster: match self.bpb.fat_type {
+                FatType::Fat16 => 0,
+                FatType::Fat32 => self.bpb.root_cluster,
+            },
+            size: 0,
+        }
+    }
+
+    fn read_dir(&mut self, dir: &FatDirEntry) -> Result<Vec<FatDirEntry>, ErrorCode> {
+        // FAT16 keeps the root directory in a fixed region before the data area
+        if dir.first_cluster == 0 {
+            let raw = self.read_sectors(self.bpb.root_dir_start, self.bpb.root_dir_sectors)?;
+            return Ok(parse_dir_entries(&raw));
+        }
+
+        let mut raw = vec![];
+        for cluster in self.cluster_chain(dir.first_cluster)? {
+            raw.extend_from_slice(&self.read_cluster(cluster)?);
+        }
+
+        Ok(parse_dir_entries(&raw))
+    }
+
+    fn resolve(&mut self, path: &Path) -> Result<FatDirEntry, ErrorCode> {
+        let mut entry = self.root_entry();
+
+        for segment in path.segments() {
+            if entry.typ != FSNodeType::Dir {
+                return Err(ErrorCode::ENOTDIR);
+            }
+
+            // FAT names are case insensitive
+            entry = self
+                .read_dir(&entry)?
+                .into_iter()
+                .find(|child| child.name.eq_ignore_ascii_case(segment))
+                .ok_or(ErrorCode::ENOENT)?;
+        }
+
+        Ok(entry)
+    }
+}
+
+impl Filesystem for FatFS {
+    fn root(&self, arc_ref: Arc<Mutex<Box<dyn Filesystem>>>) -> FSNode {
+        FSNode {
+            name: String::from("/"),
+            path: Path::new("/"),
+            typ: FSNodeType::Dir,
+            fs: arc_ref.clone(),
+        }
+    }
+
+    fn open(&mut self, path: Path, arc_ref: Arc<Mutex<Box<dyn Filesystem>>>) -> IOResult {
+        let entry = self.resolve(&path)?;
+
+        Ok(FSNode {
+            name: entry.name,
+            typ: entry.typ,
+            fs: arc_ref,
+            path,
+        })
+    }
+
+    fn create_file(&mut self, _: Path, _: Arc<Mutex<Box<dyn Filesystem>>>) -> IOResult {
+        Err(ErrorCode::EROFS)
+    }
+
+    fn create_dir(&mut self, _: Path, _: Arc<Mutex<Box<dyn Filesystem>>>) -> IOResult {
+        Err(ErrorCode::EROFS)
+    }
+
+    fn list_path(
+        &mut self,
+        path: Path,
+        arc_ref: Arc<Mutex<Box<dyn Filesystem>>>,
+    ) -> Result<Vec<FSNode>, ErrorCode> {
+        let dir = self.resolve(&path)?;
+        if dir.typ != FSNodeType::Dir {
+            return Err(ErrorCode::ENOTDIR);
+        }
+
+        let fsnodes = self
+            .read_dir(&dir)?
+            .into_iter()
+            .map(|child| FSNode {
+                path: path.append(&child.name),
+                name: child.name,
+                typ: child.typ,
+                fs: arc_ref.clone(),
+            })
+            .collect();
+
+        Ok(fsnodes)
+    }
+
+    fn write(&mut self, _: &FSNode, _: Vec<u8>, _: usize, _: usize) -> Result<usize, ErrorCode> {
+        Err(ErrorCode::EROFS)
+    }
+
+    fn read(&mut self, node: &FSNode, start: usize, end: usize) -> Result<Vec<u8>, ErrorCode> {
+        let entry = self.resolve(&node.path)?;
+        if entry.typ == FSNodeType::Dir {
+            return Err(ErrorCode::EISDIR);
+        }
+        if start > end || end >= entry.size {
+            return Err(ErrorCode::EINVAL);
+        }
+
+        let cluster_size = self.bpb.cluster_size();
+        let first = start / cluster_size;
+        let last = end / cluster_size;
+
+        let mut content = vec![];
+        for cluster in self
+            .cluster_chain(entry.first_cluster)?
+            .into_iter()
+            .skip(first)
+            .take(last - first + 1)
+        {
+            content.extend_from_slice(&self.read_cluster(cluster)?);
+        }
+
+        let offset = first * cluster_size;
+        content
+            .get(start - offset..end - offset + 1)
+            .map(|bytes| bytes.to_vec())
+            .ok_or(ErrorCode::EIO)
+    }
+
+    fn fsize(&mut self, path: Path) -> Result<usize, ErrorCode> {
+        let entry = self.resolve(&path)?;
+        match entry.typ {
+            FSNodeType::Dir => Err(ErrorCode::EISDIR),
+            _ => Ok(entry.size),
+        }
+    }
+
+    fn truncate(&mut self, _: &FSNode, _: usize) -> Result<(), ErrorCode> {
+        Err(ErrorCode::EROFS)
+    }
+
+    fn metadata(&mut self, path: Path) -> Result<Metadata, ErrorCode> {
+        let entry = self.resolve(&path)?;
+
+        Ok(Metadata {
+            size: entry.size,
+            typ: entry.typ,
+            created: None,
+            modified: None,
+        })
+    }
+
+    fn close(&mut self, _: FSNode) {
+        // do nothing
+    }
+
+    fn unmount(&mut self) {
+        // do nothing
+    }
+}
+
+/// Parses raw directory entries, joining long file name entries with their short entry
+fn parse_dir_entries(raw: &[u8]) -> Vec<FatDirEntry> {
+    let mut entries = vec![];
+    // (sequence number, checksum, name characters) of the pending long name parts
+    let mut long_name_parts: Vec<(u8, u8, [u16; 13])> = vec![];
+
+    for entry in raw.chunks_exact(DIR_ENTRY_SIZE) {
+        match entry[0] {
+            ENTRY_END => break,
+            ENTRY_DELETED => {
+                long_name_parts.clear();
+                continue;
+            }
+            _ => {}
+        }
+
+        let attributes = entry[11];
+        if attributes & ATTR_LONG_NAME == ATTR_LONG_NAME {
+            let mut chars = [0u16; 13];
+            let char_offsets = (1..11)
+                .step_by(2)
+                .chain((14..26).step_by(2))
+                .chain((28..32).step_by(2));
+            for (c, offset) in chars.iter_mut().zip(char_offsets) {
+                *c = read_u16(entry, offset);
+            }
+
+            long_name_parts.push((entry[0] & 0x1f, entry[13], chars));
+            continue;
+        }
+
+        let long_name_parts = core::mem::take(&mut long_name_parts);
+        if attributes & ATTR_VOLUME_ID != 0 {
+            continue;
+        }
+
+        let short_name: [u8; 11] = entry[0..11].try_into().unwrap();
+        if short_name[0] == b'.' {
+            // skip `.` and `..`
+            continue;
+        }
+
+        let name = assemble_long_name(long_name_parts, short_name_checksum(&short_name))
+            .unwrap_or_else(|| format_short_name(&short_name));
+        let first_cluster = (read_u16(entry, 20) as u32) << 16 | read_u16(entry, 26) as u32;
+
+        entries.push(FatDirEntry {
+            name,
+            typ: if attributes & ATTR_DIRECTORY != 0 {
+                FSNodeType::Dir
+            } else {
+                FSNodeType::File
+            },
+            first_cluster,
+            size: read_u32(entry, 28) as usize,
+        });
+    }
+
+    entries
+}
+
+/// Joins long name parts, returns `None` if there are none or they don't belong to the
+/// short entry with the given checksum
+fn assemble_long_name(mut parts: Vec<(u8, u8, [u16; 13])>, checksum: u8) -> Option<String> {
+    if parts.is_empty()
+        || parts
+            .iter()
+            .any(|(_, part_checksum, _)| *part_checksum != checksum)
+    {
+        return None;
+    }
+
+    parts.sort_by_key(|(sequence, _, _)| *sequence);
+    let chars = parts
+        .iter()
+        .flat_map(|(_, _, chars)| chars.iter().copied())
+        .take_while(|c| *c != 0x0000 && *c != 0xffff);
+
+    char::decode_utf16(chars)
+        .collect::<Result<String, _>>()
+        .ok()
+}
+
+fn short_name_checksum(short_name: &[u8; 11]) -> u8 {
+    short_name
+        .iter()
+        .fold(0u8, |sum, byte| sum.rotate_right(1).wrapping_add(*byte))
+}
+
+fn format_short_name(short_name: &[u8; 11]) -> String {
+    let mut base = short_name[0..8].to_vec();
+    // 0x05 stands in for a leading 0xe5, which marks deleted entries
+    if base[0] == 0x05 {
+        base[0] = 0xe5;
+    }
+
+    let base = String::from_utf8_lossy(&base);
+    let extension = String::from_utf8_lossy(&short_name[8..11]);
+    let base = base.trim_end();
+    let extension = extension.trim_end();
+
+    if extension.is_empty() {
+        String::from(base)
+    } else {
+        format!("{}.{}", base, extension)
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
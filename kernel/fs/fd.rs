@@ -0,0 +1,112 @@
+use crate::fs::path::Path;
+use crate::fs::vfs::VFS;
+use crate::fs::FSNode;
+use crate::kutils::errors::ErrorCode;
+
+/// Bytes [`FdTable::read_to_end`] asks for at a time
+const READ_CHUNK_SIZE: usize = 512;
+
+/// Index into an [`FdTable`]
+pub type Fd = usize;
+
+struct OpenFile {
+    node: FSNode,
+    offset: usize,
+}
+
+/// Maps small integers to open files and their current offsets
+pub struct FdTable {
+    files: Vec<Option<OpenFile>>,
+}
+
+#[allow(unused)]
+impl FdTable {
+    pub fn new() -> Self {
+        Self { files: vec![] }
+    }
+
+    /// Opens `path` and returns the lowest free descriptor for it
+    pub fn open(&mut self, path: Path) -> Result<Fd, ErrorCode> {
+        let node = VFS.lock().open(path)?;
+        let file = Some(OpenFile { node, offset: 0 });
+
+        match self.files.iter().position(Option::is_none) {
+            Some(fd) => {
+                self.files[fd] = file;
+                Ok(fd)
+            }
+            None => {
+                self.files.push(file);
+                Ok(self.files.len() - 1)
+            }
+        }
+    }
+
+    /// Reads into `buf` from the current offset and advances it.
+    /// Returns the number of bytes read, 0 at the end of the file.
+    pub fn read(&mut self, fd: Fd, buf: &mut [u8]) -> Result<usize, ErrorCode> {
+        let file = self.get_mut(fd)?;
+        let read = VFS.lock().read_at(&file.node, file.offset, buf)?;
+        file.offset += read;
+
+        Ok(read)
+    }
+
+    /// Reads from the current offset until the end of the file
+    pub fn read_to_end(&mut self, fd: Fd) -> Result<Vec<u8>, ErrorCode> {
+        let mut contents = vec![];
+        let mut chunk = [0; READ_CHUNK_SIZE];
+        loop {
+            let read = self.read(fd, &mut chunk)?;
+            if read == 0 {
+                return Ok(contents);
+            }
+            contents.extend_from_slice(&chunk[..read]);
+        }
+    }
+
+    /// Writes `buf` at the current offset and advances it past the written bytes
+    pub fn write(&mut self, fd: Fd, buf: &[u8]) -> Result<usize, ErrorCode> {
+        let file = self.get_mut(fd)?;
+        let written = VFS.lock().write_at(&file.node, file.offset, buf)?;
+        file.offset += written;
+
+        Ok(written)
+    }
+
+    /// Writes `buf` at the end of the file and moves the offset past it
+    pub fn append(&mut self, fd: Fd, buf: &[u8]) -> Result<usize, ErrorCode> {
+        let file = self.get_mut(fd)?;
+        let written = VFS.lock().append(&file.node, buf)?;
+        // node paths are relative to their mountpoint, ask the filesystem directly
+        file.offset = file.node.fs.lock().fsize(file.node.path())?;
+
+        Ok(written)
+    }
+
+    /// Cuts the file down to `size` bytes. The offset is left alone, like `ftruncate`.
+    pub fn truncate(&mut self, fd: Fd, size: usize) -> Result<(), ErrorCode> {
+        let file = self.get_mut(fd)?;
+        VFS.lock().truncate(&file.node, size)
+    }
+
+    pub fn close(&mut self, fd: Fd) -> Result<(), ErrorCode> {
+        let file = self
+            .files
+            .get_mut(fd)
+            .and_then(Option::take)
+            .ok_or(ErrorCode::EBADF)?;
+
+        let fs = file.node.fs.clone();
+        fs.lock().close(file.node);
+
+        Ok(())
+    }
+
+    fn get_mut(&mut self, fd: Fd) -> Result<&mut OpenFile, ErrorCode> {
+        self.files
+            .get_mut(fd)
+            .and_then(Option::as_mut)
+            .ok_or(ErrorCode::EBADF)
+    }
+}
@@ -0,0 +1,47 @@
+use crate::fs::path::Path;
+use crate::fs::tar;
+use crate::fs::vfs::VFS;
+use crate::kutils::errors::ErrorCode;
+use crate::kutils::multiboot::get_initrd_start_end;
+use multiboot2::BootInformation;
+
+/// The initrd the bootloader loaded as the first module, if any.
+///
+/// The module is identity mapped and its frames are never handed out, see `mm::init`.
+pub fn initrd_bytes(boot_info: &BootInformation) -> Option<&'static [u8]> {
+    let (start, end) = get_initrd_start_end(boot_info)?;
+
+    Some(unsafe { core::slice::from_raw_parts(start as *const u8, end - start + 1) })
+}
+
+/// Unpacks a USTAR archive into the filesystem mounted at `/`.
+///
+/// Returns the number of files and directories created.
+pub fn unpack(archive: &[u8]) -> Result<usize, ErrorCode> {
+    let mut created = 0;
+
+    for entry in tar::parse(archive)? {
+        // `Path` would clamp `..` at the root and the entry would land on some other path
+        if entry.path.split('/').any(|segment| segment == "..") {
+            warn!("Skipping initrd entry outside the archive: {}", entry.path);
+            continue;
+        }
+        let path = Path::new("/").join(&Path::new(&entry.path));
+
+        if entry.is_dir {
+            VFS.lock().create_dir_all(path)?;
+        } else if let Some(parent) = path.parent() {
+            VFS.lock().create_dir_all(parent)?;
+
+            let file = VFS.lock().create_file(path)?;
+            if !entry.data.is_empty() {
+                VFS.lock()
+                    .write(&file, entry.data.to_vec(), 0, entry.data.len() - 1)?;
+            }
+        }
+
+        created += 1;
+    }
+
+    Ok(created)
+}
@@ -1,44 +1,46 @@
 use core::fmt::{Display, Formatter};
 
-/// This represents a path. Given path will be made absolute when
-/// `new` is called.
+/// This represents a path. Paths starting with `/` are absolute, anything else is relative
+/// and can be resolved against a directory with `join`.
 ///
-/// # NOTE
-/// This isn't meant to represent paths like `/../xyz/abc` or `../xyz/`.
-/// Caller must include the path before`../`.
-/// Meaning, an ideal path can look like this `/home/user/../xyz/abc`. That means, `..` isn't allowed
-/// to be the first segment of a path.
+/// `..` at the root of an absolute path stays at the root like it does on POSIX, so `/../xyz`
+/// is `/xyz`. Relative paths keep their leading `..`, e.g. `../xyz`.
 #[derive(Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub struct Path {
     segments: Vec<String>,
+    absolute: bool,
 }
 
 #[allow(unused)]
 impl Path {
     pub fn new(path: &str) -> Self {
+        let absolute = path.starts_with('/');
         let segments = path
             .split('/')
             .filter(|segment| *segment != ".") // '.' is kinda "no op" so we ignore them
             .filter(|segment| !segment.is_empty()) // Hack to remove empty strings when path is split
             .map(String::from)
             .collect::<Vec<_>>();
-        assert_ne!(segments.first(), Some(&String::from("..")));
-        let segments = Self::canonicalize(segments);
+        let segments = Self::canonicalize(segments, absolute);
 
-        Self { segments }
+        Self { segments, absolute }
     }
 
     /// Takes path segments and makes the path canonical
-    fn canonicalize(segments: Vec<String>) -> Vec<String> {
+    fn canonicalize(segments: Vec<String>, absolute: bool) -> Vec<String> {
         // We are at the root
         if segments.is_empty() {
             return segments;
         }
 
-        // go through the path and remove previous segment if current segment is ".."
-        let mut new_segment = vec![];
+        // go through the path and remove previous segment if current segment is "..".
+        // ".." at the root of an absolute path removes nothing, relative paths keep leading
+        // ".." segments since there's nothing to remove yet.
+        let mut new_segment: Vec<String> = vec![];
         for segment in segments {
-            if segment == ".." {
+            if segment != ".." {
+                new_segment.push(segment);
+            } else if new_segment.last().map_or(absolute, |last| last != "..") {
                 new_segment.pop();
             } else {
                 new_segment.push(segment);
@@ -48,6 +50,29 @@ impl Path {
         new_segment
     }
 
+    pub fn is_absolute(&self) -> bool {
+        self.absolute
+    }
+
+    /// Resolves `other` against this path. Returns `other` if it's absolute.
+    pub fn join(&self, other: &Path) -> Path {
+        if other.absolute {
+            return other.clone();
+        }
+
+        let segments = self
+            .segments
+            .iter()
+            .chain(other.segments.iter())
+            .cloned()
+            .collect();
+
+        Self {
+            segments: Self::canonicalize(segments, self.absolute),
+            absolute: self.absolute,
+        }
+    }
+
     pub fn segments(&self) -> &[String] {
         &self.segments
     }
@@ -71,9 +96,25 @@ impl Path {
 
         Self {
             segments: new_segment,
+            absolute: true,
         }
     }
 
+    /// Returns the directory containing this path, or `None` for the root
+    pub fn parent(&self) -> Option<Path> {
+        let (_, parent) = self.segments.split_last()?;
+
+        Some(Self {
+            segments: parent.to_vec(),
+            absolute: self.absolute,
+        })
+    }
+
+    /// Returns the last segment of this path, or `None` for the root
+    pub fn file_name(&self) -> Option<&str> {
+        self.segments.last().map(String::as_str)
+    }
+
     pub fn append(&self, segment: &str) -> Path {
         Path::new(&format!("{}/{}", self, segment))
     }
@@ -81,6 +122,12 @@ impl Path {
 
 impl Display for Path {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
-        write!(f, "/{}", self.segments.join("/"))
+        if self.absolute {
+            write!(f, "/{}", self.segments.join("/"))
+        } else if self.segments.is_empty() {
+            write!(f, ".")
+        } else {
+            write!(f, "{}", self.segments.join("/"))
+        }
     }
 }
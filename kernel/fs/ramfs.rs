@@ -1,5 +1,6 @@
+use crate::arch::interrupts::pit8254::get_global_ms;
 use crate::fs::path::Path;
-use crate::fs::{FSNode, FSNodeType, Filesystem, IOResult};
+use crate::fs::{FSNode, FSNodeType, Filesystem, IOResult, Metadata};
 use crate::kutils::errors::ErrorCode;
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
@@ -15,6 +16,7 @@ impl RamFS {
             root: RamFSNode::Dir(RamFSDir {
                 children: BTreeMap::new(),
                 name: String::from("/"),
+                created: get_global_ms(),
             }),
         }
     }
@@ -26,7 +28,7 @@ impl RamFS {
             match node {
                 RamFSNode::File(_) => Err(ErrorCode::ENOENT),
                 RamFSNode::Dir(dir) => {
-                    let Some(entry) = dir.children.get_mut(path.segments().last().unwrap()) else {
+                    let Some(entry) = dir.children.get_mut(path.file_name().unwrap()) else {
                         return Err(ErrorCode::ENOENT);
                     };
 
@@ -79,28 +81,28 @@ impl Filesystem for RamFS {
     }
 
     fn create_file(&mut self, path: Path, arc_ref: Arc<Mutex<Box<dyn Filesystem>>>) -> IOResult {
-        let dir_path = {
-            if path.segments().len() <= 1 {
-                Path::new("/")
-            } else {
-                path.path_from_range(0, path.segments().len() - 2)
-            }
+        // Only the root has neither, and it always exists
+        let (Some(dir_path), Some(name)) = (path.parent(), path.file_name()) else {
+            return Err(ErrorCode::EEXIST);
         };
+        let name = String::from(name);
         let result = Self::resolve(dir_path, &mut self.root)?;
         let dir = match result {
             RamFSNode::Dir(dir) => dir,
             RamFSNode::File(_) => return Err(ErrorCode::ENOENT),
         };
         dir.children.insert(
-            path.segments().last().unwrap().clone(),
+            name.clone(),
             RamFSNode::File(RamFSFile {
-                name: path.segments().last().unwrap().clone(),
+                name: name.clone(),
                 content: Vec::new(),
+                created: get_global_ms(),
+                modified: get_global_ms(),
             }),
         );
 
         Ok(FSNode {
-            name: path.segments().last().unwrap().clone(),
+            name,
             typ: FSNodeType::File,
             fs: arc_ref.clone(),
             path,
@@ -108,28 +110,27 @@ impl Filesystem for RamFS {
     }
 
     fn create_dir(&mut self, path: Path, arc_ref: Arc<Mutex<Box<dyn Filesystem>>>) -> IOResult {
-        let dir_path = {
-            if path.segments().len() <= 1 {
-                Path::new("/")
-            } else {
-                path.path_from_range(0, path.segments().len() - 2)
-            }
+        // Only the root has neither, and it always exists
+        let (Some(dir_path), Some(name)) = (path.parent(), path.file_name()) else {
+            return Err(ErrorCode::EEXIST);
         };
+        let name = String::from(name);
         let result = Self::resolve(dir_path, &mut self.root)?;
         let dir = match result {
             RamFSNode::Dir(dir) => dir,
             RamFSNode::File(_) => return Err(ErrorCode::ENOENT),
         };
         dir.children.insert(
-            path.segments().last().unwrap().clone(),
+            name.clone(),
             RamFSNode::Dir(RamFSDir {
-                name: path.segments().last().unwrap().clone(),
+                name: name.clone(),
                 children: BTreeMap::new(),
+                created: get_global_ms(),
             }),
         );
 
         Ok(FSNode {
-            name: path.segments().last().unwrap().clone(),
+            name,
             typ: FSNodeType::Dir,
             fs: arc_ref.clone(),
             path,
@@ -170,19 +171,23 @@ impl Filesystem for RamFS {
         if bytes.is_empty() {
             return Ok(0);
         }
+        if start > end || bytes.len() < end + 1 - start {
+            return Err(ErrorCode::EINVAL);
+        }
         let file = Self::resolve(node.path.clone(), &mut self.root)?;
         let f = match file {
             RamFSNode::Dir(_) => return Err(ErrorCode::EISDIR),
             RamFSNode::File(f) => f,
         };
-        let bytes_increased = (end + 1).saturating_sub(f.content.len());
-        for _ in 0..bytes_increased {
-            f.content.push(0);
+        if f.content.len() < end + 1 {
+            f.content.resize(end + 1, 0);
         }
 
-        f.content[start..(end + 1)].copy_from_slice(&bytes[..(end + 1 - start)]);
+        let written = end + 1 - start;
+        f.content[start..(end + 1)].copy_from_slice(&bytes[..written]);
+        f.modified = get_global_ms();
 
-        Ok(bytes_increased)
+        Ok(written)
     }
 
     fn read(&mut self, node: &FSNode, start: usize, end: usize) -> Result<Vec<u8>, ErrorCode> {
@@ -207,6 +212,36 @@ impl Filesystem for RamFS {
         }
     }
 
+    fn truncate(&mut self, node: &FSNode, size: usize) -> Result<(), ErrorCode> {
+        match Self::resolve(node.path.clone(), &mut self.root)? {
+            RamFSNode::Dir(_) => Err(ErrorCode::EISDIR),
+            RamFSNode::File(f) => {
+                f.content.resize(size, 0);
+                f.modified = get_global_ms();
+                Ok(())
+            }
+        }
+    }
+
+    fn metadata(&mut self, path: Path) -> Result<Metadata, ErrorCode> {
+        let metadata = match Self::resolve(path, &mut self.root)? {
+            RamFSNode::Dir(d) => Metadata {
+                size: 0,
+                typ: FSNodeType::Dir,
+                created: Some(d.created),
+                modified: Some(d.created),
+            },
+            RamFSNode::File(f) => Metadata {
+                size: f.content.len(),
+                typ: FSNodeType::File,
+                created: Some(f.created),
+                modified: Some(f.modified),
+            },
+        };
+
+        Ok(metadata)
+    }
+
     fn close(&mut self, _: FSNode) {
         // do nothing
     }
@@ -220,12 +255,17 @@ impl Filesystem for RamFS {
 struct RamFSDir {
     name: String,
     children: BTreeMap<String, RamFSNode>,
+    /// Milliseconds since boot
+    created: u64,
 }
 
 #[derive(Debug)]
 struct RamFSFile {
     name: String,
     content: Vec<u8>,
+    /// Milliseconds since boot
+    created: u64,
+    modified: u64,
 }
 
 #[derive(Debug)]
@@ -0,0 +1,102 @@
+use crate::kutils::align::align_up;
+use crate::kutils::errors::ErrorCode;
+
+const BLOCK_SIZE: usize = 512;
+
+const TYPE_FILE: u8 = b'0';
+/// Pre-POSIX tar writers mark regular files with a NUL type
+const TYPE_FILE_OLD: u8 = 0;
+const TYPE_DIR: u8 = b'5';
+
+/// A regular file or directory in a USTAR archive
+#[derive(Debug)]
+pub struct TarEntry<'a> {
+    /// Path inside the archive with the prefix field prepended, e.g. `etc/motd`
+    pub path: String,
+    pub is_dir: bool,
+    pub data: &'a [u8],
+}
+
+/// Parses a USTAR archive. Entries other than regular files and directories are skipped.
+///
+/// Fails with `EINVAL` on a bad header checksum, a malformed octal field, or data running
+/// past the end of the archive.
+pub fn parse(bytes: &[u8]) -> Result<Vec<TarEntry<'_>>, ErrorCode> {
+    let mut entries = vec![];
+    let mut offset = 0;
+
+    while let Some(header) = bytes.get(offset..offset + BLOCK_SIZE) {
+        // the archive ends with zero filled blocks
+        if header.iter().all(|byte| *byte == 0) {
+            break;
+        }
+
+        if parse_octal(&header[148..156]) != Some(checksum(header)) {
+            return Err(ErrorCode::EINVAL);
+        }
+
+        let size = parse_octal(&header[124..136]).ok_or(ErrorCode::EINVAL)?;
+        let data_start = offset + BLOCK_SIZE;
+        let data = bytes
+            .get(data_start..data_start + size)
+            .ok_or(ErrorCode::EINVAL)?;
+        offset = data_start + align_up(size, BLOCK_SIZE);
+
+        let is_dir = match header[156] {
+            TYPE_DIR => true,
+            TYPE_FILE | TYPE_FILE_OLD => false,
+            _ => continue,
+        };
+
+        let name = c_str(&header[0..100])?;
+        let path = match &header[257..262] {
+            b"ustar" if header[345] != 0 => format!("{}/{}", c_str(&header[345..500])?, name),
+            _ => String::from(name),
+        };
+
+        entries.push(TarEntry { path, is_dir, data });
+    }
+
+    Ok(entries)
+}
+
+/// Sum of all header bytes, with the checksum field itself counted as spaces
+fn checksum(header: &[u8]) -> usize {
+    header
+        .iter()
+        .enumerate()
+        .map(|(idx, byte)| match idx {
+            148..=155 => b' ' as usize,
+            _ => *byte as usize,
+        })
+        .sum()
+}
+
+/// Parses a NUL or space terminated octal number
+fn parse_octal(field: &[u8]) -> Option<usize> {
+    let mut digits = field
+        .iter()
+        .skip_while(|byte| **byte == b' ')
+        .take_while(|byte| **byte != 0 && **byte != b' ')
+        .peekable();
+    digits.peek()?;
+
+    let mut value: usize = 0;
+    for digit in digits {
+        if !(b'0'..=b'7').contains(digit) {
+            return None;
+        }
+
+        value = value.checked_mul(8)?.checked_add((digit - b'0') as usize)?;
+    }
+
+    Some(value)
+}
+
+fn c_str(field: &[u8]) -> Result<&str, ErrorCode> {
+    let len = field
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(field.len());
+    core::str::from_utf8(&field[..len]).map_err(|_| ErrorCode::EINVAL)
+}
@@ -1,5 +1,5 @@
 use crate::fs::path::Path;
-use crate::fs::{FSNode, Filesystem, IOResult};
+use crate::fs::{FSNode, Filesystem, IOResult, Metadata};
 use crate::kutils::errors::ErrorCode;
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
@@ -24,18 +24,27 @@ impl Vfs {
         }
     }
 
+    /// Finds the filesystem mounted closest to `path` and the path relative to its mountpoint
     #[allow(clippy::type_complexity)]
     fn resolve_mountpoint(
         &mut self,
         path: Path,
     ) -> Result<(Arc<Mutex<Box<dyn Filesystem>>>, Path), ErrorCode> {
-        if let Some(mountpoint) = self.mounts.get(&path) {
-            Ok((mountpoint.clone(), Path::new("/")))
-        } else if let Some(mountpoint) = self.mounts.get(&Path::new("/")) {
-            Ok((mountpoint.clone(), path))
+        let (mount_path, mountpoint) = self
+            .mounts
+            .iter()
+            .filter(|(mount_path, _)| path.segments().starts_with(mount_path.segments()))
+            .max_by_key(|(mount_path, _)| mount_path.segments().len())
+            .ok_or(ErrorCode::ENOENT)?;
+
+        let depth = mount_path.segments().len();
+        let path_in_mountpoint = if depth == path.segments().len() {
+            Path::new("/")
         } else {
-            Err(ErrorCode::ENOENT)
-        }
+            path.path_from_idx(depth)
+        };
+
+        Ok((mountpoint.clone(), path_in_mountpoint))
     }
 
     pub fn open(&mut self, path: Path) -> IOResult {
@@ -69,6 +78,40 @@ impl Vfs {
         mountpoint_locked.read(fsnode, start, end)
     }
 
+    pub fn read_at(
+        &mut self,
+        fsnode: &FSNode,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> Result<usize, ErrorCode> {
+        let mountpoint = fsnode.fs.clone();
+        let mut mountpoint_locked = mountpoint.lock();
+        mountpoint_locked.read_at(fsnode, offset, buf)
+    }
+
+    pub fn write_at(
+        &mut self,
+        fsnode: &FSNode,
+        offset: usize,
+        bytes: &[u8],
+    ) -> Result<usize, ErrorCode> {
+        let mountpoint = fsnode.fs.clone();
+        let mut mountpoint_locked = mountpoint.lock();
+        mountpoint_locked.write_at(fsnode, offset, bytes)
+    }
+
+    pub fn truncate(&mut self, fsnode: &FSNode, size: usize) -> Result<(), ErrorCode> {
+        let mountpoint = fsnode.fs.clone();
+        let mut mountpoint_locked = mountpoint.lock();
+        mountpoint_locked.truncate(fsnode, size)
+    }
+
+    pub fn append(&mut self, fsnode: &FSNode, bytes: &[u8]) -> Result<usize, ErrorCode> {
+        let mountpoint = fsnode.fs.clone();
+        let mut mountpoint_locked = mountpoint.lock();
+        mountpoint_locked.append(fsnode, bytes)
+    }
+
     pub fn create_file(&mut self, path: Path) -> IOResult {
         let (mountpoint, path_in_mountpoint) = self.resolve_mountpoint(path)?;
         let node = mountpoint
@@ -85,6 +128,14 @@ impl Vfs {
         Ok(node)
     }
 
+    pub fn create_dir_all(&mut self, path: Path) -> IOResult {
+        let (mountpoint, path_in_mountpoint) = self.resolve_mountpoint(path)?;
+        let node = mountpoint
+            .lock()
+            .create_dir_all(path_in_mountpoint, mountpoint.clone())?;
+        Ok(node)
+    }
+
     pub fn list_path(&mut self, path: Path) -> Result<Vec<FSNode>, ErrorCode> {
         let (mountpoint, path_in_mountpoint) = self.resolve_mountpoint(path)?;
         let nodes = mountpoint
@@ -98,4 +149,11 @@ impl Vfs {
         let mut mountpoint_locked = mountpoint.lock();
         mountpoint_locked.fsize(path_in_mountpoint)
     }
+
+    #[allow(unused)]
+    pub fn metadata(&mut self, path: Path) -> Result<Metadata, ErrorCode> {
+        let (mountpoint, path_in_mountpoint) = self.resolve_mountpoint(path)?;
+        let mut mountpoint_locked = mountpoint.lock();
+        mountpoint_locked.metadata(path_in_mountpoint)
+    }
 }
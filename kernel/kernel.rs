@@ -2,13 +2,11 @@
 #![feature(default_alloc_error_handler)]
 #![feature(abi_x86_interrupt)]
 #![feature(prelude_import)]
-#![feature(stmt_expr_attributes)]
 #![no_std]
 
 extern crate alloc;
 
 use crate::task::executor::Executor;
-use crate::task::keyboard::print_keypresses;
 use crate::task::Task;
 use alloc::sync::Arc;
 use conquer_once::spin::OnceCell;
@@ -18,20 +16,26 @@ use spin::Mutex;
 
 use crate::arch::mm::MemoryController;
 use crate::vga::{VGADrawer, VGA_DRAWER};
-use kutils::multiboot::load_multiboot_info;
+use kutils::multiboot::{has_cmdline_flag, load_multiboot_info};
 use kutils::unsafe_sync::UnsafeSync;
 
 #[macro_use]
 mod logging;
 
 mod arch;
+mod drivers;
 mod fs;
 mod kprelude;
+#[cfg(feature = "test-mode")]
+mod ktest;
 mod kutils;
 mod panicking;
+mod shell;
 mod task;
 mod vga;
 
+use crate::fs::devfs::DevFs;
+use crate::fs::initrd;
 use crate::fs::path::Path;
 use crate::fs::ramfs::RamFS;
 use crate::fs::vfs::VFS;
@@ -42,33 +46,57 @@ use kprelude::*;
 
 #[allow(unused)]
 static NAME: &str = "FerricOxide OS";
-static MEMORY_CONTROLLER: OnceCell<UnsafeSync<MemoryController>> = OnceCell::uninit();
+static MEMORY_CONTROLLER: OnceCell<UnsafeSync<Mutex<MemoryController>>> = OnceCell::uninit();
 static BOOT_INFO: OnceCell<UnsafeSync<BootInformation>> = OnceCell::uninit();
 
 #[no_mangle]
+// test mode never gets past `ktest::run`
+#[cfg_attr(feature = "test-mode", allow(unreachable_code))]
 pub extern "C" fn kmain(multiboot_info_addr: usize) -> ! {
+    logging::serial::init();
+
     let multiboot_info = load_multiboot_info(multiboot_info_addr);
     BOOT_INFO.init_once(move || unsafe { UnsafeSync::new(multiboot_info) });
-    MEMORY_CONTROLLER.init_once(|| unsafe {
-        UnsafeSync::new(arch::initial_setup(BOOT_INFO.try_get().unwrap()))
-    });
+    #[allow(unused_mut)]
+    let mut memory_controller = arch::initial_setup(BOOT_INFO.try_get().unwrap());
     info!("Initialized architecture specific stuff");
 
-    VGADrawer::init(BOOT_INFO.try_get().unwrap());
-    info!("Initialized VGA drawer");
+    // `nofb` leaves the framebuffer alone and sends all output to serial, e.g. for headless tests
+    if has_cmdline_flag(BOOT_INFO.try_get().unwrap(), "nofb") {
+        info!("nofb is set, skipping framebuffer initialization");
+    } else {
+        VGADrawer::init(BOOT_INFO.try_get().unwrap());
+        info!("Initialized VGA drawer");
 
-    if VGA_DRAWER.lock().is_init() {
-        VGA_DRAWER.lock().buffer.clear();
-    }
-    info!("Cleared VGA drawer");
+        if VGA_DRAWER.lock().is_init() {
+            VGA_DRAWER.lock().buffer.clear();
+        }
+        info!("Cleared VGA drawer");
 
-    TextWriter::init(BOOT_INFO.try_get().unwrap());
-    info!("Initialized VGA Text writer");
+        TextWriter::init(BOOT_INFO.try_get().unwrap());
+        info!("Initialized VGA Text writer");
+    }
 
     info!("Welcome to {}!", NAME);
 
     VFS.lock()
         .mount(Path::new("/"), Arc::new(Mutex::new(Box::new(RamFS::new()))));
+    VFS.lock().mount(
+        Path::new("/dev"),
+        Arc::new(Mutex::new(Box::new(DevFs::new()))),
+    );
+
+    if let Some(initrd) = initrd::initrd_bytes(BOOT_INFO.try_get().unwrap()) {
+        match initrd::unpack(initrd) {
+            Ok(count) => info!("Unpacked {} entries from the initrd", count),
+            Err(err) => error!("Failed to unpack the initrd: {:?}", err),
+        }
+    }
+
+    #[cfg(feature = "test-mode")]
+    ktest::run(&mut memory_controller);
+
+    MEMORY_CONTROLLER.init_once(|| unsafe { UnsafeSync::new(Mutex::new(memory_controller)) });
 
     for i in 0..10 {
         VFS.lock()
@@ -104,13 +132,13 @@ pub extern "C" fn kmain(multiboot_info_addr: usize) -> ! {
     );
     let file = VFS.lock().open(Path::new("/file-0.txt")).unwrap();
     info!(
-        "Increased {} bytes in size",
+        "Wrote {} bytes",
         VFS.lock()
             .write(&file, b"Hello world!".to_vec(), 0, 11)
             .unwrap()
     );
     info!(
-        "Increased {} bytes in size",
+        "Wrote {} bytes",
         VFS.lock().write(&file, vec![], 0, 0).unwrap()
     );
     info!(
@@ -118,7 +146,7 @@ pub extern "C" fn kmain(multiboot_info_addr: usize) -> ! {
         String::from_utf8(VFS.lock().read(&file, 0, 11).unwrap()).unwrap()
     );
     info!(
-        "Replacing `H` with `R` for no reason. Increased {} bytes in size",
+        "Replacing `H` with `R` for no reason. Wrote {} bytes",
         VFS.lock().write(&file, b"R".to_vec(), 0, 0).unwrap()
     );
     info!(
@@ -127,6 +155,6 @@ pub extern "C" fn kmain(multiboot_info_addr: usize) -> ! {
     );
 
     let mut executor = Executor::new();
-    executor.spawn(Task::new(print_keypresses()));
+    executor.spawn(Task::new(shell::run()));
     executor.run();
 }
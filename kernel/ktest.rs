@@ -0,0 +1,67 @@
+//! Boot-time tests, run instead of the shell when built with the `test-mode` feature.
+//! `b.py test` boots the kernel with QEMU's exit device and reports the exit code.
+
+mod cpu;
+mod drivers;
+mod fs;
+mod interrupts;
+mod kutils;
+mod logging;
+mod mm;
+mod panicking;
+mod shell;
+mod task;
+mod vga;
+
+use crate::arch::mm::MemoryController;
+use crate::kutils::qemu::{qemu_exit, ExitCode};
+
+type Test = fn(&mut MemoryController<'_>) -> Result<(), &'static str>;
+
+static SUITES: &[&[(&str, Test)]] = &[
+    mm::TESTS,
+    interrupts::TESTS,
+    cpu::TESTS,
+    fs::TESTS,
+    drivers::TESTS,
+    kutils::TESTS,
+    logging::TESTS,
+    panicking::TESTS,
+    shell::TESTS,
+    task::TESTS,
+    vga::TESTS,
+];
+
+/// Runs every test, then exits QEMU with the overall result
+pub fn run(memory_controller: &mut MemoryController<'_>) -> ! {
+    let mut passed = 0;
+    let mut failed = 0;
+    for (name, test) in SUITES.iter().copied().flatten() {
+        match test(memory_controller) {
+            Ok(()) => {
+                test_println!("{} ... ok", name);
+                passed += 1;
+            }
+            Err(reason) => {
+                test_println!("{} ... FAILED: {}", name, reason);
+                failed += 1;
+            }
+        }
+    }
+
+    test_println!("{} passed, {} failed", passed, failed);
+    qemu_exit(if failed == 0 {
+        ExitCode::Success
+    } else {
+        ExitCode::Failed
+    });
+}
+
+/// Fails the test with `reason` unless `condition` holds
+fn check(condition: bool, reason: &'static str) -> Result<(), &'static str> {
+    if condition {
+        Ok(())
+    } else {
+        Err(reason)
+    }
+}
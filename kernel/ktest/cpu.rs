@@ -0,0 +1,200 @@
+use crate::arch::cpu::cpuid::CpuFeatures;
+use crate::arch::cpu::debug::{arm_dr7, BreakpointKind};
+use crate::arch::cpu::registers::Registers;
+use crate::arch::cpu::{percpu, Cpu};
+use crate::arch::mm::MemoryController;
+use crate::arch::x86_64::utils;
+use crate::ktest::{check, Test};
+use crate::kutils::errors::ErrorCode;
+use x86_64::instructions::interrupts;
+use x86_64::registers::model_specific::{Efer, EferFlags};
+
+pub static TESTS: &[(&str, Test)] = &[
+    ("per-CPU data", per_cpu),
+    ("without_interrupts", without_interrupts),
+    ("EFER.NXE", nxe_enabled),
+    ("TLB range flush threshold", flush_threshold),
+    ("register snapshot", register_snapshot),
+    ("DR7 encoding", dr7_encoding),
+    ("CPU feature names", cpu_feature_names),
+];
+
+fn per_cpu(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    struct Marker(usize);
+
+    check(percpu::current().cpu_id() == 0, "BSP isn't CPU 0")?;
+    check(percpu::get::<Marker>().is_none(), "unset value is there")?;
+    check(percpu::set(Marker(7)).is_ok(), "failed to set a value")?;
+    check(
+        percpu::get::<Marker>().map(|marker| marker.0) == Some(7),
+        "value didn't round trip",
+    )?;
+    check(
+        percpu::set(Marker(8)).is_err(),
+        "second value replaced the first",
+    )
+}
+
+fn without_interrupts(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(
+        interrupts::are_enabled(),
+        "interrupts are off before the test",
+    )?;
+
+    let (value, enabled_inside) = Cpu::without_interrupts(|| (42, interrupts::are_enabled()));
+    check(value == 42, "result wasn't returned")?;
+    check(!enabled_inside, "interrupts were on inside the closure")?;
+    check(interrupts::are_enabled(), "interrupts weren't re-enabled")?;
+
+    // interrupts that were off before have to stay off
+    Cpu::disable_interrupts();
+    let enabled_inside = Cpu::without_interrupts(interrupts::are_enabled);
+    let enabled_after = interrupts::are_enabled();
+    Cpu::enable_interrupts();
+    check(!enabled_inside, "interrupts were on inside the closure")?;
+    check(
+        !enabled_after,
+        "interrupts were enabled when they were off before",
+    )
+}
+
+fn nxe_enabled(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(
+        Efer::read().contains(EferFlags::NO_EXECUTE_ENABLE),
+        "NXE isn't set after boot",
+    )?;
+
+    // enabling it again must leave the other EFER bits alone
+    let before = Efer::read();
+    utils::enable_nxe_bit();
+    check(Efer::read() == before, "enabling NXE twice changed EFER")
+}
+
+fn flush_threshold(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(
+        !utils::flush_range_reloads_cr3(0),
+        "empty range reloaded CR3",
+    )?;
+    check(
+        !utils::flush_range_reloads_cr3(1),
+        "single page reloaded CR3",
+    )?;
+    check(
+        !utils::flush_range_reloads_cr3(32),
+        "range at the threshold reloaded CR3",
+    )?;
+    check(
+        utils::flush_range_reloads_cr3(33),
+        "range past the threshold was invalidated page by page",
+    )
+}
+
+fn register_snapshot(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let local = 0u64;
+    let regs = Registers::capture();
+    let local_addr = &local as *const u64 as u64;
+    check(
+        regs.rsp <= local_addr && local_addr - regs.rsp < 0x1000,
+        "captured rsp isn't on the current stack",
+    )?;
+    let test_addr = register_snapshot as *const () as u64;
+    check(
+        regs.rip >= test_addr && regs.rip - test_addr < 0x1000,
+        "captured rip isn't in the capturing function",
+    )?;
+
+    let regs = Registers {
+        rip: 0x1000,
+        rax: 0xa,
+        rbx: 0xb,
+        rcx: 0xc,
+        rdx: 0xd,
+        rsi: 0x51,
+        rdi: 0xd1,
+        rbp: 0xb9,
+        rsp: 0x59,
+        r8: 0x8,
+        r9: 0x9,
+        r10: 0x10,
+        r11: 0x11,
+        r12: 0x12,
+        r13: 0x13,
+        r14: 0x14,
+        r15: 0xffff_8000_0000_0015,
+    };
+    check(
+        format!("{}", regs)
+            == "Registers\n\
+                Instruction pointer=0x1000\n\
+                rax=0xa, rbx=0xb, rcx=0xc, rdx=0xd,\n\
+                rsi=0x51, rdi=0xd1, rbp=0xb9, rsp=0x59,\n\
+                r8=0x8, r9=0x9, r10=0x10, r11=0x11,\n\
+                r12=0x12, r13=0x13, r14=0x14, r15=0xffff800000000015\n",
+        "formatted registers don't match the fields",
+    )
+}
+
+fn dr7_encoding(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    // L0, R/W0 = 00(execute), LEN0 = 00(1 byte)
+    check(
+        arm_dr7(0, 0, 0x1000, BreakpointKind::Exec, 1) == Ok(0x1),
+        "slot 0 execute",
+    )?;
+    // L1, R/W1 = 01(write), LEN1 = 11(4 bytes)
+    check(
+        arm_dr7(0, 1, 0x1000, BreakpointKind::Write, 4) == Ok(0x00d0_0004),
+        "slot 1 write of 4 bytes",
+    )?;
+    // L2, R/W2 = 01(write), LEN2 = 01(2 bytes)
+    check(
+        arm_dr7(0, 2, 0x1000, BreakpointKind::Write, 2) == Ok(0x0500_0010),
+        "slot 2 write of 2 bytes",
+    )?;
+    // L3, R/W3 = 11(read or write), LEN3 = 10(8 bytes)
+    check(
+        arm_dr7(0, 3, 0x1000, BreakpointKind::ReadWrite, 8) == Ok(0xb000_0040),
+        "slot 3 read or write of 8 bytes",
+    )?;
+
+    check(
+        arm_dr7(0x1, 1, 0x1000, BreakpointKind::Write, 4) == Ok(0x00d0_0005),
+        "arming a slot disturbed another one",
+    )?;
+    check(
+        arm_dr7(0xffd0_0004, 1, 0x1000, BreakpointKind::ReadWrite, 1) == Ok(0xff30_0004),
+        "old R/W and LEN bits of the slot weren't replaced",
+    )?;
+
+    check(
+        arm_dr7(0, 4, 0x1000, BreakpointKind::Exec, 1) == Err(ErrorCode::EINVAL),
+        "slot past DR3 was accepted",
+    )?;
+    check(
+        arm_dr7(0, 0, 0x1000, BreakpointKind::Write, 3) == Err(ErrorCode::EINVAL),
+        "3 byte length was accepted",
+    )?;
+    check(
+        arm_dr7(0, 0, 0x1000, BreakpointKind::Exec, 4) == Err(ErrorCode::EINVAL),
+        "execute breakpoint longer than a byte was accepted",
+    )?;
+    check(
+        arm_dr7(0, 0, 0x1002, BreakpointKind::Write, 4) == Err(ErrorCode::EINVAL),
+        "misaligned data breakpoint was accepted",
+    )
+}
+
+fn cpu_feature_names(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let features = CpuFeatures::FPU | CpuFeatures::SSE2 | CpuFeatures::HYPERVISOR;
+    check(
+        features.names().collect::<Vec<_>>() == ["fpu", "sse2", "hypervisor"],
+        "set features weren't listed in bit order",
+    )?;
+    check(
+        CpuFeatures::empty().names().next().is_none(),
+        "no features listed something",
+    )?;
+    check(
+        CpuFeatures::all().names().count() == CpuFeatures::all().bits().count_ones() as usize,
+        "a feature has no name",
+    )
+}
@@ -0,0 +1,52 @@
+use crate::arch::mm::MemoryController;
+use crate::drivers::ata::{
+    drive_select_byte, has_failed, is_busy, is_ready_for_data, lba28_bytes, AtaDrive,
+};
+use crate::ktest::{check, Test};
+
+pub static TESTS: &[(&str, Test)] = &[
+    ("ata register bytes", ata_register_bytes),
+    ("ata status predicates", ata_status_predicates),
+];
+
+fn ata_register_bytes(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(
+        lba28_bytes(0x0123_4567) == [0x67, 0x45, 0x23, 0x01],
+        "28-bit LBA split",
+    )?;
+    check(
+        lba28_bytes(0x1_2345_6789) == [0x89, 0x67, 0x45, 0x03],
+        "bits above 28 aren't dropped",
+    )?;
+    check(
+        drive_select_byte(AtaDrive::Master, 0x0123_4567, true) == 0xe1,
+        "master in LBA mode",
+    )?;
+    check(
+        drive_select_byte(AtaDrive::Slave, 0x0123_4567, true) == 0xf1,
+        "slave in LBA mode",
+    )?;
+    check(
+        drive_select_byte(AtaDrive::Master, 0x0123_4567, false) == 0xa0,
+        "master in CHS mode",
+    )?;
+    check(
+        drive_select_byte(AtaDrive::Slave, 0, false) == 0xb0,
+        "slave in CHS mode",
+    )
+}
+
+fn ata_status_predicates(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    const BSY: u8 = 0x80;
+    const DF: u8 = 0x20;
+    const DRQ: u8 = 0x08;
+    const ERR: u8 = 0x01;
+
+    check(is_busy(BSY) && !is_busy(DRQ), "BSY bit")?;
+    check(is_ready_for_data(DRQ), "DRQ without BSY")?;
+    check(!is_ready_for_data(BSY | DRQ), "DRQ while busy")?;
+    check(has_failed(ERR) && has_failed(DF), "ERR and DF bits")?;
+    // the other bits aren't valid while the drive is busy
+    check(!has_failed(BSY | ERR), "ERR while busy")?;
+    check(!has_failed(DRQ), "DRQ alone isn't a failure")
+}
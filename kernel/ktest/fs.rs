@@ -0,0 +1,671 @@
+use crate::arch::mm::MemoryController;
+use crate::fs::block::{read_bytes, BlockDevice, MemBlockDevice};
+use crate::fs::cwd::Cwd;
+use crate::fs::fat::FatFS;
+use crate::fs::fd::FdTable;
+use crate::fs::initrd;
+use crate::fs::path::Path;
+use crate::fs::vfs::VFS;
+use crate::fs::{tar, walk, FSNodeType, Filesystem};
+use crate::ktest::{check, Test};
+use crate::kutils::errors::ErrorCode;
+use alloc::sync::Arc;
+use spin::Mutex;
+
+pub static TESTS: &[(&str, Test)] = &[
+    ("ramfs", ramfs),
+    ("path dot-dot at root", path_dot_dot_at_root),
+    ("cwd", cwd),
+    ("fat long file names", fat_long_file_names),
+    ("block device boundaries", block_device_boundaries),
+    ("fd reuse and double close", fd_reuse_and_double_close),
+    ("fd sequential reads", fd_sequential_reads),
+    ("read_at and write_at at EOF", read_write_at_eof),
+    ("devfs", devfs),
+    ("metadata", metadata),
+    ("create_dir_all", create_dir_all),
+    ("walk", walk_tree),
+    ("initrd unpack", initrd_unpack),
+    ("tar entries", tar_entries),
+    ("tar bad checksum", tar_bad_checksum),
+    ("path parent and file name", path_parent_file_name),
+    ("path join and is_absolute", path_join_is_absolute),
+];
+
+const SECTOR_SIZE: usize = 512;
+
+fn ramfs(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    const CONTENT: &[u8] = b"ktest";
+
+    let path = Path::new("/ktest.txt");
+    let mut vfs = VFS.lock();
+    vfs.create_file(path.clone())
+        .map_err(|_| "failed to create file")?;
+    let file = vfs.open(path).map_err(|_| "failed to open file")?;
+    check(
+        vfs.write(&file, CONTENT.to_vec(), 0, CONTENT.len() - 1) == Ok(CONTENT.len()),
+        "extending write didn't count every byte",
+    )?;
+    let read = vfs
+        .read(&file, 0, CONTENT.len() - 1)
+        .map_err(|_| "failed to read file")?;
+    check(
+        read == CONTENT,
+        "read back different content than was written",
+    )?;
+
+    // overwriting doesn't grow the file but still writes bytes
+    check(
+        vfs.write(&file, b"TE".to_vec(), 1, 2) == Ok(2),
+        "overwrite didn't count the bytes written",
+    )?;
+    let read = vfs
+        .read(&file, 0, CONTENT.len() - 1)
+        .map_err(|_| "failed to read file")?;
+    check(read == b"kTEst", "overwrite went to the wrong place")?;
+
+    check(
+        vfs.write(&file, b"x".to_vec(), 3, 2) == Err(ErrorCode::EINVAL),
+        "backwards range didn't fail with EINVAL",
+    )?;
+    check(
+        vfs.write(&file, b"xy".to_vec(), 0, 2) == Err(ErrorCode::EINVAL),
+        "range longer than the bytes didn't fail with EINVAL",
+    )?;
+    let read = vfs
+        .read(&file, 0, CONTENT.len() - 1)
+        .map_err(|_| "failed to read file")?;
+    check(read == b"kTEst", "rejected write changed the file")
+}
+
+fn path_dot_dot_at_root(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(Path::new("/..") == Path::new("/"), "/..")?;
+    check(Path::new("/../x") == Path::new("/x"), "/../x")?;
+    check(Path::new("/a/../../b") == Path::new("/b"), "/a/../../b")?;
+    check(
+        Path::new("/").join(&Path::new("../..")) == Path::new("/"),
+        "joining ../.. onto /",
+    )
+}
+
+fn cwd(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    VFS.lock()
+        .create_dir_all(Path::new("/ktest-cwd/sub"))
+        .map_err(|_| "failed to create directories")?;
+    VFS.lock()
+        .create_file(Path::new("/ktest-cwd/file"))
+        .map_err(|_| "failed to create file")?;
+
+    let mut cwd = Cwd::new();
+    check(cwd.chdir("..").is_ok(), "cd .. at the root")?;
+    check(*cwd.path() == Path::new("/"), "cd .. left the root")?;
+
+    check(cwd.chdir("ktest-cwd").is_ok(), "cd into a relative dir")?;
+    check(
+        cwd.resolve("sub/../file") == Path::new("/ktest-cwd/file"),
+        "relative resolution",
+    )?;
+    check(
+        cwd.resolve("/x") == Path::new("/x"),
+        "absolute input ignores the cwd",
+    )?;
+    check(
+        cwd.chdir("file") == Err(ErrorCode::ENOTDIR),
+        "cd into a file",
+    )?;
+    check(
+        cwd.chdir("missing") == Err(ErrorCode::ENOENT),
+        "cd into a missing dir",
+    )?;
+    check(
+        *cwd.path() == Path::new("/ktest-cwd"),
+        "failed cd changed the cwd",
+    )
+}
+
+/// Reads a file with a long name from a minimal FAT16 image built in memory
+fn fat_long_file_names(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    const LONG_NAME: &str = "A long file name.txt";
+    const SHORT_NAME: &[u8; 11] = b"ALONGF~1TXT";
+    const CONTENT: &[u8] = b"long names work";
+
+    // 1 reserved sector, one FAT, a one sector root directory and just enough one sector
+    // clusters for the volume to count as FAT16
+    const FAT_SECTORS: usize = 16;
+    const ROOT_DIR_SECTOR: usize = 1 + FAT_SECTORS;
+    const DATA_SECTOR: usize = ROOT_DIR_SECTOR + 1;
+    const TOTAL_SECTORS: usize = DATA_SECTOR + 4085;
+
+    let mut image = vec![0u8; TOTAL_SECTORS * SECTOR_SIZE];
+
+    let boot_sector = &mut image[..SECTOR_SIZE];
+    boot_sector[11..13].copy_from_slice(&(SECTOR_SIZE as u16).to_le_bytes());
+    boot_sector[13] = 1;
+    boot_sector[14..16].copy_from_slice(&1u16.to_le_bytes());
+    boot_sector[16] = 1;
+    boot_sector[17..19].copy_from_slice(&16u16.to_le_bytes());
+    boot_sector[19..21].copy_from_slice(&(TOTAL_SECTORS as u16).to_le_bytes());
+    boot_sector[22..24].copy_from_slice(&(FAT_SECTORS as u16).to_le_bytes());
+    boot_sector[510..512].copy_from_slice(&[0x55, 0xaa]);
+
+    // cluster 2 holds the whole file
+    let fat = &mut image[SECTOR_SIZE..2 * SECTOR_SIZE];
+    fat[4..6].copy_from_slice(&0xffffu16.to_le_bytes());
+
+    let checksum = SHORT_NAME
+        .iter()
+        .fold(0u8, |sum, byte| sum.rotate_right(1).wrapping_add(*byte));
+    let mut name: Vec<u16> = LONG_NAME.encode_utf16().collect();
+    name.push(0);
+    name.resize(26, 0xffff);
+
+    let root_dir = &mut image[ROOT_DIR_SECTOR * SECTOR_SIZE..DATA_SECTOR * SECTOR_SIZE];
+    // long name parts come last part first
+    for (idx, part) in name.chunks(13).enumerate().rev() {
+        let entry = &mut root_dir[(1 - idx) * 32..(2 - idx) * 32];
+        entry[0] = (idx as u8 + 1) | if idx == 1 { 0x40 } else { 0 };
+        entry[11] = 0x0f;
+        entry[13] = checksum;
+
+        let offsets = (1..11)
+            .step_by(2)
+            .chain((14..26).step_by(2))
+            .chain((28..32).step_by(2));
+        for (c, offset) in part.iter().zip(offsets) {
+            entry[offset..offset + 2].copy_from_slice(&c.to_le_bytes());
+        }
+    }
+    let entry = &mut root_dir[64..96];
+    entry[..11].copy_from_slice(SHORT_NAME);
+    entry[11] = 0x20;
+    entry[26..28].copy_from_slice(&2u16.to_le_bytes());
+    entry[28..32].copy_from_slice(&(CONTENT.len() as u32).to_le_bytes());
+
+    image[DATA_SECTOR * SECTOR_SIZE..][..CONTENT.len()].copy_from_slice(CONTENT);
+
+    let device = MemBlockDevice::new(image, SECTOR_SIZE).map_err(|_| "bad image size")?;
+    let fs: Box<dyn Filesystem> =
+        Box::new(FatFS::new(Box::new(device)).map_err(|_| "failed to parse the image")?);
+    let fs = Arc::new(Mutex::new(fs));
+
+    let mut fs_ref = fs.lock();
+    let children = fs_ref
+        .list_path(Path::new("/"), fs.clone())
+        .map_err(|_| "failed to list the root directory")?;
+    check(
+        children.len() == 1
+            && children[0].name() == LONG_NAME
+            && children[0].typ() == FSNodeType::File,
+        "long name wasn't decoded",
+    )?;
+
+    // lookups ignore case
+    let file = fs_ref
+        .open(Path::new("/a LONG file name.TXT"), fs.clone())
+        .map_err(|_| "failed to open the file by its long name")?;
+    let read = fs_ref
+        .read(&file, 0, CONTENT.len() - 1)
+        .map_err(|_| "failed to read the file")?;
+    check(read == CONTENT, "read back the wrong content")
+}
+
+fn block_device_boundaries(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let image: Vec<u8> = (0..2 * SECTOR_SIZE)
+        .map(|idx| idx as u8 ^ (idx / 256) as u8)
+        .collect();
+    let mut device =
+        MemBlockDevice::new(image.clone(), SECTOR_SIZE).map_err(|_| "bad image size")?;
+
+    // unaligned read crossing from the first sector into the second
+    let read = read_bytes(&mut device, SECTOR_SIZE - 12, 24).map_err(|_| "failed to read")?;
+    check(
+        read == image[SECTOR_SIZE - 12..SECTOR_SIZE + 12],
+        "read across a sector boundary",
+    )?;
+
+    let mut sector = [0xa5; SECTOR_SIZE];
+    device
+        .write_block(1, &sector)
+        .map_err(|_| "failed to write the last sector")?;
+    sector.fill(0);
+    device
+        .read_block(1, &mut sector)
+        .map_err(|_| "failed to read the last sector")?;
+    check(
+        sector.iter().all(|byte| *byte == 0xa5),
+        "last sector didn't round trip",
+    )?;
+    check(
+        read_bytes(&mut device, 0, SECTOR_SIZE).is_ok_and(|read| read == image[..SECTOR_SIZE]),
+        "writing the last sector touched the first",
+    )?;
+
+    check(
+        device.read_block(2, &mut sector) == Err(ErrorCode::EIO),
+        "read past the end didn't fail with EIO",
+    )?;
+    check(
+        device.write_block(0, &sector[1..]) == Err(ErrorCode::EINVAL),
+        "short buffer didn't fail with EINVAL",
+    )?;
+    check(
+        MemBlockDevice::new(vec![0; SECTOR_SIZE + 1], SECTOR_SIZE).is_err(),
+        "image that isn't a whole number of sectors was accepted",
+    )
+}
+
+fn fd_reuse_and_double_close(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let path = Path::new("/ktest-fd.txt");
+    VFS.lock()
+        .create_file(path.clone())
+        .map_err(|_| "failed to create file")?;
+
+    let mut fds = FdTable::new();
+    let first = fds.open(path.clone()).map_err(|_| "failed to open file")?;
+    let second = fds.open(path.clone()).map_err(|_| "failed to open file")?;
+    check(
+        first == 0 && second == 1,
+        "descriptors aren't handed out in order",
+    )?;
+
+    fds.close(first).map_err(|_| "failed to close file")?;
+    let reused = fds.open(path).map_err(|_| "failed to open file")?;
+    check(reused == first, "lowest free descriptor wasn't reused")?;
+
+    fds.close(reused).map_err(|_| "failed to close file")?;
+    check(
+        fds.close(reused) == Err(ErrorCode::EBADF),
+        "double close didn't fail with EBADF",
+    )?;
+    check(
+        fds.read(reused, &mut [0; 1]) == Err(ErrorCode::EBADF),
+        "read from a closed descriptor didn't fail with EBADF",
+    )?;
+    fds.close(second).map_err(|_| "failed to close file")
+}
+
+fn fd_sequential_reads(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    const CONTENT: &[u8] = b"head and tail";
+
+    let path = Path::new("/ktest-fd-read.txt");
+    VFS.lock()
+        .create_file(path.clone())
+        .map_err(|_| "failed to create file")?;
+
+    let mut fds = FdTable::new();
+    let fd = fds.open(path.clone()).map_err(|_| "failed to open file")?;
+    let written = fds.write(fd, CONTENT).map_err(|_| "failed to write file")?;
+    check(written == CONTENT.len(), "short write")?;
+    fds.close(fd).map_err(|_| "failed to close file")?;
+
+    let fd = fds.open(path).map_err(|_| "failed to open file")?;
+    let mut head = [0; 5];
+    let mut tail = [0; 32];
+    let head_len = fds.read(fd, &mut head).map_err(|_| "failed to read file")?;
+    let tail_len = fds.read(fd, &mut tail).map_err(|_| "failed to read file")?;
+    let end_len = fds.read(fd, &mut tail).map_err(|_| "failed to read file")?;
+    fds.close(fd).map_err(|_| "failed to close file")?;
+
+    check(
+        head[..head_len] == CONTENT[..5],
+        "first read didn't return the head",
+    )?;
+    check(
+        tail[..tail_len] == CONTENT[5..],
+        "second read didn't continue where the first stopped",
+    )?;
+    check(end_len == 0, "read at the end of the file returned data")
+}
+
+fn read_write_at_eof(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let path = Path::new("/ktest-eof.txt");
+    let mut vfs = VFS.lock();
+    vfs.create_file(path.clone())
+        .map_err(|_| "failed to create file")?;
+    let file = vfs.open(path.clone()).map_err(|_| "failed to open file")?;
+    vfs.write_at(&file, 0, b"abcdef")
+        .map_err(|_| "failed to write file")?;
+
+    let mut buf = [0; 8];
+    let read = vfs
+        .read_at(&file, 4, &mut buf)
+        .map_err(|_| "failed to read file")?;
+    check(read == 2 && buf[..2] == *b"ef", "short read near the end")?;
+    check(
+        vfs.read_at(&file, 6, &mut buf) == Ok(0),
+        "read at the end returned data",
+    )?;
+    check(
+        vfs.read_at(&file, 100, &mut buf) == Ok(0),
+        "read past the end returned data",
+    )?;
+
+    // writing past the end extends the file and zero fills the gap
+    vfs.write_at(&file, 8, b"xy")
+        .map_err(|_| "failed to write past the end")?;
+    check(vfs.fsize(path) == Ok(10), "write didn't extend the file")?;
+    let read = vfs
+        .read_at(&file, 4, &mut buf)
+        .map_err(|_| "failed to read file")?;
+    check(
+        read == 6 && buf[..6] == *b"ef\0\0xy",
+        "extended file has the wrong content",
+    )
+}
+
+fn devfs(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let mut vfs = VFS.lock();
+    let devices = vfs
+        .list_path(Path::new("/dev"))
+        .map_err(|_| "failed to list /dev")?;
+    for name in ["console", "fb", "null"] {
+        check(
+            devices.iter().any(|device| device.name() == name),
+            "a device is missing from /dev",
+        )?;
+    }
+    check(
+        vfs.open(Path::new("/dev/console")).is_ok(),
+        "failed to open /dev/console",
+    )?;
+
+    let null = vfs
+        .open(Path::new("/dev/null"))
+        .map_err(|_| "failed to open /dev/null")?;
+    check(
+        vfs.write_at(&null, 0, b"gone") == Ok(4),
+        "/dev/null didn't take the whole write",
+    )?;
+    let mut buf = [0; 4];
+    check(
+        vfs.read_at(&null, 0, &mut buf) == Ok(0),
+        "/dev/null returned data",
+    )?;
+    check(
+        vfs.fsize(Path::new("/dev/null")) == Ok(0),
+        "/dev/null kept what was written",
+    )?;
+
+    check(
+        vfs.open(Path::new("/dev/missing"))
+            .is_err_and(|err| err == ErrorCode::ENOENT),
+        "opening a missing device didn't fail with ENOENT",
+    )?;
+    check(
+        vfs.create_file(Path::new("/dev/new"))
+            .is_err_and(|err| err == ErrorCode::EPERM),
+        "creating a file in /dev didn't fail with EPERM",
+    )
+}
+
+fn metadata(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let path = Path::new("/ktest-metadata/file");
+    let mut vfs = VFS.lock();
+    vfs.create_dir(Path::new("/ktest-metadata"))
+        .map_err(|_| "failed to create directory")?;
+    vfs.create_file(path.clone())
+        .map_err(|_| "failed to create file")?;
+    let file = vfs.open(path.clone()).map_err(|_| "failed to open file")?;
+
+    let empty = vfs
+        .metadata(path.clone())
+        .map_err(|_| "no metadata for the file")?;
+    check(
+        empty.typ == FSNodeType::File && empty.size == 0,
+        "new file isn't an empty file",
+    )?;
+
+    vfs.write_at(&file, 0, b"12345")
+        .map_err(|_| "failed to write file")?;
+    vfs.append(&file, b"678")
+        .map_err(|_| "failed to append to file")?;
+    let written = vfs.metadata(path).map_err(|_| "no metadata for the file")?;
+    check(written.size == 8, "size doesn't match what was written")?;
+    check(
+        written.created.is_some() && written.modified >= written.created,
+        "ramfs timestamps",
+    )?;
+
+    let dir = vfs
+        .metadata(Path::new("/ktest-metadata"))
+        .map_err(|_| "no metadata for the directory")?;
+    check(dir.typ == FSNodeType::Dir, "directory isn't a directory")?;
+
+    // devfs doesn't keep timestamps
+    let dev = vfs
+        .metadata(Path::new("/dev"))
+        .map_err(|_| "no metadata for /dev")?;
+    let null = vfs
+        .metadata(Path::new("/dev/null"))
+        .map_err(|_| "no metadata for /dev/null")?;
+    check(
+        dev.typ == FSNodeType::Dir && null.typ == FSNodeType::File,
+        "devfs node types",
+    )?;
+    check(
+        null.size == 0 && null.created.is_none() && null.modified.is_none(),
+        "/dev/null metadata",
+    )
+}
+
+fn create_dir_all(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let mut vfs = VFS.lock();
+    let deep = vfs
+        .create_dir_all(Path::new("/ktest-mkdir/a/b/c"))
+        .map_err(|_| "failed to create a deep path from scratch")?;
+    check(
+        deep.typ() == FSNodeType::Dir,
+        "deepest node isn't a directory",
+    )?;
+    check(
+        vfs.create_dir_all(Path::new("/ktest-mkdir/a/d")).is_ok(),
+        "failed to create over a partially existing path",
+    )?;
+    check(
+        vfs.create_dir_all(Path::new("/ktest-mkdir/a/b/c")).is_ok(),
+        "creating an existing path again failed",
+    )?;
+    check(
+        vfs.list_path(Path::new("/ktest-mkdir/a"))
+            .is_ok_and(|children| children.len() == 2),
+        "existing directories were created twice",
+    )?;
+
+    vfs.create_file(Path::new("/ktest-mkdir/file"))
+        .map_err(|_| "failed to create file")?;
+    check(
+        vfs.create_dir_all(Path::new("/ktest-mkdir/file/x"))
+            .is_err_and(|err| err == ErrorCode::ENOTDIR),
+        "file in the middle of the path didn't fail with ENOTDIR",
+    )?;
+    check(
+        vfs.create_dir_all(Path::new("/ktest-mkdir/file"))
+            .is_err_and(|err| err == ErrorCode::ENOTDIR),
+        "file at the end of the path didn't fail with ENOTDIR",
+    )
+}
+
+fn walk_tree(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    {
+        let mut vfs = VFS.lock();
+        vfs.create_dir_all(Path::new("/ktest-walk/a/b"))
+            .map_err(|_| "failed to create directories")?;
+        for file in ["/ktest-walk/x", "/ktest-walk/a/y", "/ktest-walk/a/b/z"] {
+            vfs.create_file(Path::new(file))
+                .map_err(|_| "failed to create file")?;
+        }
+    }
+
+    let mut seen = vec![];
+    let mut sizes_ok = true;
+    walk(Path::new("/ktest-walk"), &mut |node| {
+        seen.push(String::from(node.name()));
+        // the visitor may use the VFS
+        sizes_ok &= node.typ() == FSNodeType::Dir || VFS.lock().fsize(node.path()).is_ok();
+    })
+    .map_err(|_| "walk failed")?;
+
+    seen.sort();
+    check(
+        seen == ["a", "b", "x", "y", "z"],
+        "walk didn't visit every node exactly once",
+    )?;
+    check(sizes_ok, "visitor couldn't use the VFS")
+}
+
+/// Builds a USTAR archive out of `(prefix, name, typeflag, data)` entries
+fn tar_archive(entries: &[(&str, &str, u8, &[u8])]) -> Vec<u8> {
+    let mut archive = vec![];
+    for (prefix, name, typeflag, data) in entries {
+        let mut header = [0u8; SECTOR_SIZE];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..108].copy_from_slice(b"0000644\0");
+        header[124..136].copy_from_slice(format!("{:011o}\0", data.len()).as_bytes());
+        header[156] = *typeflag;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+        header[148..156].fill(b' ');
+        let sum: usize = header.iter().map(|byte| *byte as usize).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+
+        archive.extend_from_slice(&header);
+        archive.extend_from_slice(data);
+        archive.resize(archive.len().next_multiple_of(SECTOR_SIZE), 0);
+    }
+
+    // end of archive marker
+    archive.resize(archive.len() + 2 * SECTOR_SIZE, 0);
+    archive
+}
+
+fn initrd_unpack(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let archive = tar_archive(&[
+        ("", "ktest-initrd/", b'5', b""),
+        ("", "ktest-initrd/etc/motd", b'0', b"hello from the initrd"),
+        ("", "../ktest-initrd/escape", b'0', b"nope"),
+    ]);
+    check(
+        initrd::unpack(&archive) == Ok(2),
+        "wrong number of entries unpacked",
+    )?;
+
+    let mut vfs = VFS.lock();
+    check(
+        vfs.metadata(Path::new("/ktest-initrd"))
+            .is_ok_and(|metadata| metadata.typ == FSNodeType::Dir),
+        "directory entry wasn't created",
+    )?;
+    let file = vfs
+        .open(Path::new("/ktest-initrd/etc/motd"))
+        .map_err(|_| "file entry wasn't created")?;
+    let mut buf = [0; 64];
+    let read = vfs
+        .read_at(&file, 0, &mut buf)
+        .map_err(|_| "failed to read the file")?;
+    check(
+        buf[..read] == *b"hello from the initrd",
+        "file has the wrong content",
+    )?;
+    check(
+        vfs.open(Path::new("/ktest-initrd/escape")).is_err(),
+        "entry with `..` wasn't skipped",
+    )
+}
+
+fn tar_entries(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let archive = tar_archive(&[
+        ("", "motd", b'0', b"hi"),
+        ("", "etc/", b'5', b""),
+        (
+            "usr/share/doc/a-rather-long-directory-name",
+            "README",
+            b'0',
+            b"",
+        ),
+        // neither a file nor a directory, e.g. a symlink
+        ("", "link", b'2', b""),
+    ]);
+    let entries = tar::parse(&archive).map_err(|_| "failed to parse a valid archive")?;
+
+    check(entries.len() == 3, "wrong number of entries")?;
+    check(
+        entries[0].path == "motd" && !entries[0].is_dir && entries[0].data == b"hi",
+        "file entry",
+    )?;
+    check(
+        entries[1].path == "etc/" && entries[1].is_dir && entries[1].data.is_empty(),
+        "directory entry",
+    )?;
+    check(
+        entries[2].path == "usr/share/doc/a-rather-long-directory-name/README",
+        "prefix field wasn't prepended",
+    )
+}
+
+fn tar_bad_checksum(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let mut archive = tar_archive(&[("", "motd", b'0', b"hi")]);
+    check(
+        tar::parse(&archive).is_ok(),
+        "failed to parse a valid header",
+    )?;
+
+    archive[0] = b'M';
+    check(
+        tar::parse(&archive).is_err_and(|err| err == ErrorCode::EINVAL),
+        "bad checksum wasn't rejected",
+    )
+}
+
+fn path_parent_file_name(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(
+        Path::new("/a/b").parent() == Some(Path::new("/a")),
+        "parent of /a/b",
+    )?;
+    check(
+        Path::new("/a").parent() == Some(Path::new("/")),
+        "parent of /a",
+    )?;
+    check(Path::new("/").parent().is_none(), "parent of the root")?;
+    check(
+        Path::new("a/b").parent() == Some(Path::new("a")),
+        "parent of a relative path",
+    )?;
+
+    check(
+        Path::new("/a/b").file_name() == Some("b"),
+        "file name of /a/b",
+    )?;
+    check(
+        Path::new("/a/b/").file_name() == Some("b"),
+        "file name with a trailing slash",
+    )?;
+    check(
+        Path::new("/").file_name().is_none(),
+        "file name of the root",
+    )
+}
+
+fn path_join_is_absolute(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(Path::new("/x").is_absolute(), "/x isn't absolute")?;
+    check(!Path::new("x").is_absolute(), "x is absolute")?;
+    check(Path::new("/").is_absolute(), "the root isn't absolute")?;
+
+    check(
+        Path::new("/x").join(&Path::new("y")) == Path::new("/x/y"),
+        "joining a relative path",
+    )?;
+    check(
+        Path::new("/x").join(&Path::new("y/../z")) == Path::new("/x/z"),
+        "joining a relative path with ..",
+    )?;
+    check(
+        Path::new("/x").join(&Path::new("/y")) == Path::new("/y"),
+        "joining an absolute path",
+    )?;
+    check(
+        !Path::new("x").join(&Path::new("y")).is_absolute(),
+        "joining two relative paths made an absolute one",
+    )
+}
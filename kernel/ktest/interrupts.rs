@@ -0,0 +1,216 @@
+use crate::arch::interrupts::exceptions::{self, ExceptionKind, Policy, RepeatedFaults};
+use crate::arch::interrupts::pit_monitor::StallDetector;
+use crate::arch::interrupts::{lapic, pit8254, pit_monitor, single_step, PIC_1_OFFSET};
+use crate::arch::mm::MemoryController;
+use crate::ktest::{check, Test};
+use core::arch::asm;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use x86_64::instructions::tables::sidt;
+use x86_64::structures::idt::InterruptStackFrame;
+
+/// Attribute byte of a present ring 0 interrupt gate
+const INTERRUPT_GATE: u8 = 0x8e;
+/// Attribute byte of a present ring 0 trap gate
+const TRAP_GATE: u8 = 0x8f;
+
+pub static TESTS: &[(&str, Test)] = &[
+    ("timer interrupt", timer_interrupt),
+    ("PIT reload values", pit_reload_values),
+    ("uptime across a PIT rate change", uptime_across_rate_change),
+    ("PIT ticks to ms", pit_ticks_to_ms),
+    ("IDT gate types", idt_gate_types),
+    ("exception callback policy", exception_callback),
+    ("repeated fault counting", repeated_fault_counting),
+    ("trap flag", trap_flag),
+    ("PIT stall detection", pit_stall_detection),
+];
+
+fn timer_interrupt(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let before = pit8254::ticks();
+    pit8254::pit_udelay(20_000);
+
+    check(pit8254::ticks() > before, "no timer interrupt in 20ms")
+}
+
+fn pit_reload_values(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(pit8254::reload_value_for_hz(1000) == 1193, "1000Hz")?;
+    check(pit8254::reload_value_for_hz(100) == 11931, "100Hz")?;
+    check(pit8254::reload_value_for_hz(1) == 65536, "1Hz clamps up")?;
+    check(pit8254::reload_value_for_hz(0) == 65536, "0Hz")?;
+    check(
+        pit8254::reload_value_for_hz(pit8254::PIT_FREQUENCY) == 2,
+        "input clock rate clamps to mode 3's minimum",
+    )?;
+    check(pit8254::reload_value_for_hz(u32::MAX) == 2, "u32::MAX")
+}
+
+fn uptime_across_rate_change(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let before = pit8254::get_global_ms();
+    pit8254::init_with_hz(100);
+    let after = pit8254::get_global_ms();
+    pit8254::init_with_hz(pit8254::DEFAULT_FREQUENCY_HZ);
+    let restored = pit8254::get_global_ms();
+
+    check(
+        before <= after && after <= before + 10,
+        "uptime jumped when slowing the timer down",
+    )?;
+    check(
+        after <= restored && restored <= after + 10,
+        "uptime jumped when speeding the timer up",
+    )
+}
+
+fn pit_ticks_to_ms(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(
+        pit8254::ticks_to_ms(1500, 1000) == 1500,
+        "1500 ticks at 1000Hz",
+    )?;
+    check(
+        pit8254::ticks_to_ms(1500, 100) == 15000,
+        "1500 ticks at 100Hz",
+    )?;
+    check(pit8254::ticks_to_ms(0, 1000) == 0, "no ticks")?;
+    check(
+        pit8254::ticks_to_ms(u64::MAX, 1000) == u64::MAX,
+        "huge tick counts overflowed",
+    )?;
+    check(
+        pit8254::frequency_hz() == pit8254::DEFAULT_FREQUENCY_HZ,
+        "configured rate isn't the one used for the conversion",
+    )
+}
+
+/// Attribute byte(present, DPL and gate type) of `vector` in the loaded IDT
+fn gate_attributes(vector: u8) -> u8 {
+    let idt = sidt();
+    // SAFETY: every vector has a 16 byte descriptor in the loaded IDT
+    unsafe { *(idt.base.as_u64() as *const u8).add(vector as usize * 16 + 5) }
+}
+
+fn idt_gate_types(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(
+        gate_attributes(3) == TRAP_GATE,
+        "breakpoint isn't a trap gate",
+    )?;
+
+    let interrupt_gates = [
+        0,  // divide error
+        1,  // debug
+        8,  // double fault
+        13, // general protection fault
+        14, // page fault
+        PIC_1_OFFSET,
+        PIC_1_OFFSET + 1,
+        pit_monitor::PIT_MONITOR_VECTOR,
+        lapic::SPURIOUS_VECTOR,
+    ];
+    for vector in interrupt_gates {
+        check(
+            gate_attributes(vector) == INTERRUPT_GATE,
+            "handler isn't an interrupt gate",
+        )?;
+    }
+
+    Ok(())
+}
+
+static DIVIDE_ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+/// Skips the two byte `div ecx` in [`exception_callback`]
+fn skip_div(kind: ExceptionKind, stack_frame: &mut InterruptStackFrame) {
+    if kind == ExceptionKind::DivideError {
+        DIVIDE_ERRORS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    unsafe {
+        stack_frame
+            .as_mut()
+            .update(|frame| frame.instruction_pointer += 2u64);
+    }
+}
+
+fn exception_callback(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    exceptions::set_exception_policy(ExceptionKind::DivideError, Policy::Callback(skip_div));
+    unsafe {
+        asm!(
+            "xor edx, edx",
+            "mov eax, 1",
+            "xor ecx, ecx",
+            "div ecx",
+            out("eax") _,
+            out("ecx") _,
+            out("edx") _,
+        );
+    }
+    exceptions::set_exception_policy(ExceptionKind::DivideError, Policy::Halt);
+
+    check(
+        DIVIDE_ERRORS.load(Ordering::SeqCst) == 1,
+        "callback wasn't called exactly once",
+    )
+}
+
+fn repeated_fault_counting(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let mut faults = RepeatedFaults::new();
+    check(
+        faults.record(ExceptionKind::DivideError, 0x1000) == 1,
+        "first fault",
+    )?;
+    check(
+        faults.record(ExceptionKind::DivideError, 0x1000) == 2,
+        "same instruction again",
+    )?;
+    check(
+        faults.record(ExceptionKind::GeneralProtection, 0x1000) == 1,
+        "another exception at the same instruction continued the count",
+    )?;
+    check(
+        faults.record(ExceptionKind::GeneralProtection, 0x1002) == 1,
+        "another instruction continued the count",
+    )
+}
+
+fn trap_flag(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    // IF and a reserved bit that always reads as one
+    let flags = 0x202;
+    check(
+        single_step::with_trap_flag(flags, true) == 0x302,
+        "TF wasn't set",
+    )?;
+    check(
+        single_step::with_trap_flag(0x302, false) == flags,
+        "TF wasn't cleared",
+    )?;
+    check(
+        single_step::with_trap_flag(0x302, true) == 0x302,
+        "setting TF twice changed the flags",
+    )?;
+    check(
+        single_step::with_trap_flag(flags, false) == flags,
+        "clearing a clear TF changed the flags",
+    )
+}
+
+fn pit_stall_detection(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let stalls = StallDetector::new();
+    stalls.reset(100);
+
+    check(!stalls.observe(1100), "ticking PIT was reported")?;
+    check(stalls.observe(1100), "stall wasn't reported")?;
+    check(
+        !stalls.observe(1100) && !stalls.observe(1100),
+        "the same stall was reported twice",
+    )?;
+    check(!stalls.observe(1101), "recovered PIT was reported")?;
+    check(
+        stalls.observe(1101),
+        "stall after recovering wasn't reported",
+    )?;
+
+    stalls.reset(5);
+    check(
+        stalls.observe(5),
+        "reset didn't start over from the given ticks",
+    )
+}
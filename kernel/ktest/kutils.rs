@@ -0,0 +1,64 @@
+use crate::arch::mm::MemoryController;
+use crate::ktest::{check, Test};
+use crate::kutils::multiboot::cmdline_has_word;
+use crate::kutils::units::{format_bytes, format_duration, GiB, KiB, MiB};
+
+pub static TESTS: &[(&str, Test)] = &[
+    ("format_bytes units", format_bytes_units),
+    ("format_duration", format_durations),
+    ("command line flags", cmdline_flags),
+];
+
+fn format_bytes_units(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let cases = [
+        (0, "0 B"),
+        (KiB - 1, "1023 B"),
+        (KiB, "1.00 KiB"),
+        (KiB + KiB / 2, "1.50 KiB"),
+        (MiB - 1, "1023.99 KiB"),
+        (MiB, "1.00 MiB"),
+        (GiB - 1, "1023.99 MiB"),
+        (GiB, "1.00 GiB"),
+        (16 * GiB, "16.00 GiB"),
+    ];
+
+    for (bytes, expected) in cases {
+        check(
+            format!("{}", format_bytes(bytes)) == expected,
+            "wrong unit or rounding",
+        )?;
+    }
+
+    Ok(())
+}
+
+fn format_durations(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let cases = [
+        (0, "0h 00m 00.000s"),
+        (59_999, "0h 00m 59.999s"),
+        (60_000, "0h 01m 00.000s"),
+        (3_661_500, "1h 01m 01.500s"),
+        (100 * 3_600_000, "100h 00m 00.000s"),
+    ];
+
+    for (ms, expected) in cases {
+        check(
+            format!("{}", format_duration(ms)) == expected,
+            "wrong hours, minutes or seconds",
+        )?;
+    }
+
+    Ok(())
+}
+
+fn cmdline_flags(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(cmdline_has_word("nofb", "nofb"), "exact word")?;
+    check(
+        cmdline_has_word("  quiet\tnofb  loglevel=3", "nofb"),
+        "word among others",
+    )?;
+    check(!cmdline_has_word("nofbx", "nofb"), "prefix of a word")?;
+    check(!cmdline_has_word("xnofb", "nofb"), "suffix of a word")?;
+    check(!cmdline_has_word("", "nofb"), "empty command line")?;
+    check(!cmdline_has_word("nofb", ""), "empty flag matched a word")
+}
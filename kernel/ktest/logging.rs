@@ -0,0 +1,114 @@
+use crate::arch::mm::MemoryController;
+use crate::ktest::{check, Test};
+use crate::logging::serial::{divisor_for, is_transmit_ready, passes_self_test};
+use crate::logging::{log_enabled, set_log_level, Level, Timestamp};
+use core::fmt::Arguments;
+use spin::Mutex;
+
+pub static TESTS: &[(&str, Test)] = &[
+    ("log level", log_level),
+    ("log timestamps", log_timestamps),
+    ("UART self-test comparison", uart_self_test),
+    ("UART baud divisors", uart_divisors),
+    ("failed kassert", failed_kassert),
+    ("UART transmit ready", uart_transmit_ready),
+];
+
+fn log_level(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(
+        !log_enabled(Level::Debug) && log_enabled(Level::Info),
+        "default level isn't Info",
+    )?;
+
+    set_log_level(Level::Warn);
+    let info = log_enabled(Level::Info);
+    let warn = log_enabled(Level::Warn);
+    let error = log_enabled(Level::Error);
+    set_log_level(Level::Info);
+
+    check(!info, "message below the level wasn't dropped")?;
+    check(warn && error, "message at or above the level was dropped")
+}
+
+fn log_timestamps(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(
+        format!("{}", Timestamp(Some(12345))) == "[   12.345] ",
+        "seconds and milliseconds",
+    )?;
+    check(
+        format!("{}", Timestamp(Some(7))) == "[    0.007] ",
+        "milliseconds get zero padded",
+    )?;
+    check(
+        format!("{}", Timestamp(Some(123_456_789))) == "[123456.789] ",
+        "more seconds than the padding",
+    )?;
+    check(
+        format!("{}", Timestamp(None)).is_empty(),
+        "disabled timestamp printed something",
+    )
+}
+
+fn uart_self_test(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(passes_self_test(0xAE), "echoed byte was rejected")?;
+    // a missing UART usually reads back as all ones, a dead one as zero
+    check(
+        !passes_self_test(0xFF) && !passes_self_test(0x00),
+        "floating bus was taken for a UART",
+    )?;
+    check(!passes_self_test(0xAF), "wrong byte was accepted")
+}
+
+fn uart_divisors(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(divisor_for(115200) == Some(1), "115200 baud")?;
+    check(divisor_for(38400) == Some(3), "38400 baud")?;
+    check(divisor_for(9600) == Some(12), "9600 baud")?;
+    check(divisor_for(0).is_none(), "0 baud was accepted")?;
+    check(
+        divisor_for(230400).is_none(),
+        "baud above the UART clock was accepted",
+    )?;
+    check(
+        divisor_for(7).is_none(),
+        "baud that doesn't divide the clock was accepted",
+    )
+}
+
+/// Message of the last failed assertion in [`failed_kassert`]
+static ASSERTION: Mutex<Option<String>> = Mutex::new(None);
+
+fn record_assertion(message: Arguments) {
+    *ASSERTION.lock() = Some(format!("{}", message));
+}
+
+fn failed_kassert(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let x = 5;
+    kassert!(@fail record_assertion; x == 5, "x is {}", x);
+    check(
+        ASSERTION.lock().take().is_none(),
+        "passing assertion recorded a failure",
+    )?;
+
+    kassert!(@fail record_assertion; x == 6, "x is {}", x);
+    check(
+        ASSERTION.lock().take().as_deref() == Some("Assertion failed: x == 6: x is 5"),
+        "message of a failed assertion",
+    )?;
+
+    kassert!(@fail record_assertion; x > 10);
+    check(
+        ASSERTION.lock().take().as_deref() == Some("Assertion failed: x > 10"),
+        "failed assertion without a message",
+    )
+}
+
+fn uart_transmit_ready(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(is_transmit_ready(0x20), "empty holding register")?;
+    // transmitter idle and data ready as well
+    check(is_transmit_ready(0x61), "other status bits hid bit 5")?;
+    check(!is_transmit_ready(0x00), "busy holding register")?;
+    check(
+        !is_transmit_ready(0xdf),
+        "other status bits were taken for bit 5",
+    )
+}
@@ -0,0 +1,381 @@
+use crate::arch::mm::address_space::AddressSpace;
+use crate::arch::mm::paging::entry::{CacheMode, EntryFlags};
+use crate::arch::mm::paging::mapper::{coalesce_mappings, Mapper, MappingRun};
+use crate::arch::mm::paging::{is_canonical, translate_addr, Page, TEMPORARY_PAGE_ADDRESS};
+use crate::arch::mm::{Frame, MemStats, MemoryController, HEAP_START, PAGE_SIZE};
+use crate::ktest::{check, Test};
+use crate::kutils::errors::ErrorCode;
+use crate::kutils::units::{KiB, MiB};
+
+/// First page of P4 slot 1, which the kernel doesn't use
+const USER_PAGE_ADDRESS: usize = 1 << 39;
+/// Physical range in the PCI hole below 4GiB that nothing else identity maps
+const SCRATCH_MMIO_ADDRESS: usize = 0xfeb0_0000;
+
+pub static TESTS: &[(&str, Test)] = &[
+    ("memory", memory),
+    ("temporary page", temporary_page),
+    ("address space regions", address_space_regions),
+    ("address space switch", address_space_switch),
+    ("address space drop", address_space_drop),
+    ("with_phys_mapped", with_phys_mapped),
+    ("identity map twice", identity_map_twice),
+    ("cache mode bits", cache_mode_bits),
+    ("try_map_to over a mapping", try_map_to_over_mapping),
+    ("page and frame debug output", page_and_frame_debug),
+    ("translate_with_flags", translate_with_flags),
+    ("mapping runs", mapping_runs),
+    ("canonical addresses", canonical_addresses),
+    ("map_mmio rollback", map_mmio_rollback),
+    ("memory stats display", mem_stats_display),
+];
+
+fn memory(memory_controller: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(memory_controller.self_test(), "memory self test failed")
+}
+
+/// Building and changing another address space maps the temporary page over and over
+fn temporary_page(memory_controller: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let page = Page::containing_address(USER_PAGE_ADDRESS);
+
+    let mut address_space = AddressSpace::new(memory_controller);
+    address_space
+        .map_region(memory_controller, page, page, EntryFlags::WRITABLE)
+        .map_err(|_| "failed to map a region")?;
+    address_space
+        .unmap_region(memory_controller, page)
+        .map_err(|_| "failed to unmap a region")?;
+
+    check(
+        translate_addr(TEMPORARY_PAGE_ADDRESS).is_none(),
+        "temporary page is still mapped",
+    )?;
+    check(
+        translate_addr(USER_PAGE_ADDRESS).is_none(),
+        "region leaked into the active address space",
+    )
+}
+
+/// Overlapping and kernel regions are rejected, the rest are listed in mapping order
+fn address_space_regions(memory_controller: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let first = Page::containing_address(USER_PAGE_ADDRESS);
+    let second = first + 4;
+
+    let mut address_space = AddressSpace::new(memory_controller);
+    address_space
+        .map_region(memory_controller, first, first + 1, EntryFlags::WRITABLE)
+        .map_err(|_| "failed to map the first region")?;
+    address_space
+        .map_region(memory_controller, second, second, EntryFlags::empty())
+        .map_err(|_| "failed to map the second region")?;
+
+    check(
+        address_space.map_region(memory_controller, first + 1, second, EntryFlags::empty())
+            == Err(ErrorCode::EEXIST),
+        "overlapping region wasn't rejected",
+    )?;
+    check(
+        address_space.map_region(
+            memory_controller,
+            Page::containing_address(MiB),
+            Page::containing_address(MiB),
+            EntryFlags::empty(),
+        ) == Err(ErrorCode::EFAULT),
+        "region in a kernel P4 slot wasn't rejected",
+    )?;
+
+    let regions = address_space.regions();
+    check(regions.len() == 2, "wrong number of regions")?;
+    check(
+        regions[0].start() == first
+            && regions[0].end() == first + 1
+            && regions[0].flags() == EntryFlags::WRITABLE,
+        "first region",
+    )?;
+    check(
+        regions[1].start() == second
+            && regions[1].end() == second
+            && regions[1].flags() == EntryFlags::empty(),
+        "second region",
+    )?;
+
+    address_space
+        .unmap_region(memory_controller, first)
+        .map_err(|_| "failed to unmap the first region")?;
+    check(
+        address_space.regions().len() == 1 && address_space.regions()[0].start() == second,
+        "unmapped region is still listed",
+    )?;
+    check(
+        address_space.unmap_region(memory_controller, first) == Err(ErrorCode::ENOENT),
+        "unmapping a missing region",
+    )
+}
+
+/// Regions mapped while an address space is active go into the live table
+fn address_space_switch(memory_controller: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let page = Page::containing_address(USER_PAGE_ADDRESS);
+
+    let mut address_space = AddressSpace::new(memory_controller);
+    address_space.switch_to(memory_controller);
+    let mapped = address_space
+        .map_region(memory_controller, page, page, EntryFlags::WRITABLE)
+        .is_ok();
+    let visible = translate_addr(USER_PAGE_ADDRESS).is_some();
+    address_space.switch_back(memory_controller);
+
+    check(mapped, "failed to map a region while active")?;
+    check(visible, "region isn't mapped in the active table")?;
+    check(
+        translate_addr(USER_PAGE_ADDRESS).is_none(),
+        "region is still mapped after switching back",
+    )?;
+    address_space
+        .unmap_region(memory_controller, page)
+        .map_err(|_| "failed to unmap the region")
+}
+
+/// Dropped address spaces give back their page tables and mapped frames
+fn address_space_drop(memory_controller: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let page = Page::containing_address(USER_PAGE_ADDRESS);
+
+    AddressSpace::reap_retired(memory_controller);
+    let before = memory_controller.stats().allocated_frames;
+
+    let mut address_space = AddressSpace::new(memory_controller);
+    address_space
+        .map_region(memory_controller, page, page + 3, EntryFlags::WRITABLE)
+        .map_err(|_| "failed to map a region")?;
+    check(
+        memory_controller.stats().allocated_frames > before,
+        "mapping didn't allocate frames",
+    )?;
+    drop(address_space);
+
+    AddressSpace::reap_retired(memory_controller);
+    check(
+        memory_controller.stats().allocated_frames == before,
+        "frames of a dropped address space weren't freed",
+    )
+}
+
+/// Writes to a heap buffer through its physical frame and reads it back through the heap
+fn with_phys_mapped(memory_controller: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    const PATTERN: [u8; 4] = [0xde, 0xad, 0xbe, 0xef];
+
+    let buffer = Box::new([0u8; PATTERN.len()]);
+    let phys = translate_addr(buffer.as_ptr() as usize).ok_or("heap buffer isn't mapped")?;
+    let offset = phys % PAGE_SIZE;
+
+    let window_phys = memory_controller.with_phys_mapped(phys, |window| {
+        window[offset..offset + PATTERN.len()].copy_from_slice(&PATTERN);
+        translate_addr(window.as_ptr() as usize)
+    });
+
+    check(
+        window_phys == Some(phys - offset),
+        "window isn't mapped to the requested frame",
+    )?;
+    check(
+        // volatile, the compiler can't see the write through the window. SAFETY: buffer is live
+        unsafe { core::ptr::read_volatile(buffer.as_ptr() as *const [u8; PATTERN.len()]) }
+            == PATTERN,
+        "write through the window didn't reach the frame",
+    )?;
+    check(
+        translate_addr(TEMPORARY_PAGE_ADDRESS).is_none(),
+        "temporary page is still mapped",
+    )
+}
+
+/// Mapping a range twice is fine, identity mapping over another mapping isn't
+fn identity_map_twice(memory_controller: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    for _ in 0..2 {
+        memory_controller
+            .map_mmio(SCRATCH_MMIO_ADDRESS, 2 * PAGE_SIZE)
+            .map_err(|_| "failed to identity map the scratch range")?;
+    }
+    check(
+        translate_addr(SCRATCH_MMIO_ADDRESS + PAGE_SIZE) == Some(SCRATCH_MMIO_ADDRESS + PAGE_SIZE),
+        "scratch range isn't identity mapped",
+    )?;
+
+    // the heap's virtual address is backed by some other frame. With that much RAM it's
+    // usable memory though, which map_mmio may refuse to reserve before ever mapping it
+    if memory_controller.is_in_memory_map(HEAP_START, PAGE_SIZE) {
+        return Ok(());
+    }
+    let heap_frame = translate_addr(HEAP_START);
+    check(
+        memory_controller.map_mmio(HEAP_START, PAGE_SIZE) == Err(ErrorCode::EEXIST),
+        "identity mapping over the heap didn't fail",
+    )?;
+    check(
+        translate_addr(HEAP_START) == heap_frame,
+        "failed identity map changed the heap mapping",
+    )
+}
+
+/// PAT entries 0, 1 and 3 as programmed by `init_pat`
+fn cache_mode_bits(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(
+        EntryFlags::from_cache_mode(CacheMode::WriteBack).is_empty(),
+        "write-back",
+    )?;
+    check(
+        EntryFlags::from_cache_mode(CacheMode::WriteCombining) == EntryFlags::WRITE_THROUGH,
+        "write-combining",
+    )?;
+    check(
+        EntryFlags::from_cache_mode(CacheMode::Uncacheable)
+            == EntryFlags::WRITE_THROUGH | EntryFlags::NO_CACHE,
+        "uncacheable",
+    )
+}
+
+fn try_map_to_over_mapping(
+    memory_controller: &mut MemoryController<'_>,
+) -> Result<(), &'static str> {
+    memory_controller.remap_test()
+}
+
+fn page_and_frame_debug(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(
+        format!("{:?}", Page::containing_address(0x1234_5678)) == "Page(virt=0x12345000)",
+        "page",
+    )?;
+    check(
+        format!("{:?}", Frame::containing_address(0xb8005)) == "Frame(phys=0xb8000)",
+        "frame",
+    )
+}
+
+fn translate_with_flags(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    // SAFETY: the recursive mapping is always present and we only read through it
+    let mapper = unsafe { Mapper::new() };
+
+    let text = translate_with_flags as fn(&mut MemoryController<'_>) -> _ as usize;
+    let (phys, flags) = mapper
+        .translate_with_flags(text)
+        .ok_or("kernel text isn't mapped")?;
+    check(phys == text, "kernel text isn't identity mapped")?;
+    check(
+        flags.contains(EntryFlags::PRESENT)
+            && !flags.contains(EntryFlags::WRITABLE)
+            && !flags.contains(EntryFlags::NO_EXECUTE),
+        "kernel text should be read-only and executable",
+    )?;
+
+    let (_, flags) = mapper
+        .translate_with_flags(HEAP_START + 0x123)
+        .ok_or("heap isn't mapped")?;
+    check(
+        flags.contains(EntryFlags::PRESENT | EntryFlags::WRITABLE),
+        "heap should be writable",
+    )?;
+
+    check(
+        mapper.translate_with_flags(USER_PAGE_ADDRESS).is_none(),
+        "unmapped address has flags",
+    )
+}
+
+fn mapping_runs(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let page = Page::containing_address(USER_PAGE_ADDRESS);
+    let frame = |number: usize| Frame::containing_address(number * PAGE_SIZE);
+    let writable = EntryFlags::PRESENT | EntryFlags::WRITABLE;
+    let read_only = EntryFlags::PRESENT;
+
+    let runs = coalesce_mappings([
+        (page, Some((frame(10), writable))),
+        (page + 1, Some((frame(11), writable))),
+        // different flags
+        (page + 2, Some((frame(12), read_only))),
+        (page + 3, None),
+        // the frame follows, but the page doesn't
+        (page + 4, Some((frame(13), read_only))),
+        // the page follows, but the frame doesn't
+        (page + 5, Some((frame(20), read_only))),
+    ]);
+
+    let run = |page, frame, count, flags| MappingRun {
+        page,
+        frame,
+        count,
+        flags,
+    };
+    check(
+        runs == [
+            run(page, frame(10), 2, writable),
+            run(page + 2, frame(12), 1, read_only),
+            run(page + 4, frame(13), 1, read_only),
+            run(page + 5, frame(20), 1, read_only),
+        ],
+        "pages were merged into the wrong runs",
+    )
+}
+
+fn canonical_addresses(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    for address in [0, 0x7fff_ffff_ffff, 0xffff_8000_0000_0000, usize::MAX] {
+        check(is_canonical(address), "canonical address was rejected")?;
+    }
+    for address in [
+        0x8000_0000_0000,
+        0xffff_7fff_ffff_ffff,
+        0x1234_0000_0000_0000,
+    ] {
+        check(!is_canonical(address), "non-canonical address was accepted")?;
+    }
+
+    // the upper half indexes from the top of the P4, the recursive slot is the last one
+    let top = Page::containing_address(0xffff_ff80_0000_0000);
+    check(top.p4_index() == 511, "P4 index of the recursive slot")?;
+    check(
+        Page::containing_address(0x7fff_ffff_f000).p4_index() == 255,
+        "P4 index at the top of the lower half",
+    )
+}
+
+/// A range whose last page overlaps another mapping must leave nothing mapped or reserved
+fn map_mmio_rollback(memory_controller: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let start = HEAP_START - 2 * PAGE_SIZE;
+    if memory_controller.is_in_memory_map(start, 3 * PAGE_SIZE)
+        || translate_addr(start).is_some()
+        || translate_addr(start + PAGE_SIZE).is_some()
+    {
+        // same as in identity_map_twice, the overlap can't be set up here
+        return Ok(());
+    }
+
+    check(
+        memory_controller.map_mmio(start, 3 * PAGE_SIZE) == Err(ErrorCode::EEXIST),
+        "range overlapping the heap didn't fail",
+    )?;
+    check(
+        translate_addr(start).is_none() && translate_addr(start + PAGE_SIZE).is_none(),
+        "pages mapped before the overlap stayed mapped",
+    )?;
+    check(
+        !memory_controller.is_reserved(start),
+        "failed mapping kept its reservation",
+    )
+}
+
+fn mem_stats_display(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let stats = MemStats {
+        total: 128 * MiB,
+        free: 96 * MiB,
+        used: 32 * MiB,
+        allocated_frames: 8192,
+        heap_used: 1536,
+        heap_free: 99 * KiB,
+    };
+
+    check(
+        format!("{}", stats)
+            == "physical: 128.00 MiB total, 32.00 MiB used, 96.00 MiB free\n\
+                frames:   8192 allocated\n\
+                heap:     1.50 KiB used, 99.00 KiB free\n",
+        "formatted stats don't match the fields",
+    )
+}
@@ -0,0 +1,39 @@
+use crate::arch::mm::MemoryController;
+use crate::ktest::{check, Test};
+use crate::panicking::{panic_path, try_paint_panic_screen, PanicPath};
+use crate::vga::VGA_DRAWER;
+use core::sync::atomic::AtomicBool;
+
+pub static TESTS: &[(&str, Test)] = &[
+    (
+        "panic screen with the framebuffer locked",
+        panic_screen_locked,
+    ),
+    ("nested panic", nested_panic),
+];
+
+/// A panic while drawing has the framebuffer locked, painting must give up instead of
+/// deadlocking
+fn panic_screen_locked(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let _drawer = VGA_DRAWER.lock();
+    let mut wrote = false;
+    let painted = try_paint_panic_screen(|_| wrote = true);
+
+    check(!painted && !wrote, "painted over a locked framebuffer")
+}
+
+fn nested_panic(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let in_panic = AtomicBool::new(false);
+    check(
+        panic_path(&in_panic) == PanicPath::Full,
+        "first panic took the minimal path",
+    )?;
+    check(
+        panic_path(&in_panic) == PanicPath::Minimal,
+        "nested panic took the full path",
+    )?;
+    check(
+        panic_path(&in_panic) == PanicPath::Minimal,
+        "third panic took the full path",
+    )
+}
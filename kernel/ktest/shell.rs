@@ -0,0 +1,276 @@
+use crate::arch::mm::{range_in_areas, MemoryController};
+use crate::arch::power;
+use crate::fs::fd::FdTable;
+use crate::fs::path::Path;
+use crate::fs::vfs::VFS;
+use crate::ktest::{check, Test};
+use crate::kutils::errors::ErrorCode;
+use crate::shell::{execute, parse_number, parse_peek_args, split_line};
+
+pub static TESTS: &[(&str, Test)] = &[
+    ("parse_number", parse_numbers),
+    ("peek arguments", peek_arguments),
+    ("peek range validation", peek_range_validation),
+    ("unknown command", unknown_command),
+    ("argument splitting", argument_splitting),
+    ("cat", cat_command),
+    ("ls", ls_command),
+    ("echo", echo_command),
+    ("echo truncation and append", echo_truncate_append),
+    ("shutdown ports", shutdown_ports),
+    ("peek range edges", peek_range_edges),
+];
+
+fn parse_numbers(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(parse_number("4096") == Ok(4096), "decimal")?;
+    check(parse_number("0x1000") == Ok(0x1000), "hex")?;
+    check(parse_number("0xFfF") == Ok(0xfff), "mixed case hex")?;
+    check(parse_number("0x") == Err(ErrorCode::EINVAL), "empty hex")?;
+    check(
+        parse_number("12ab") == Err(ErrorCode::EINVAL),
+        "hex without prefix",
+    )?;
+    check(parse_number("-1") == Err(ErrorCode::EINVAL), "negative")
+}
+
+fn peek_arguments(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(
+        parse_peek_args(&["0x100000", "16"]) == Ok((0x100000, 16)),
+        "hex address and decimal length",
+    )?;
+    check(
+        parse_peek_args(&["0x100000", "0x1000"]) == Ok((0x100000, 0x1000)),
+        "one page",
+    )?;
+    check(
+        parse_peek_args(&["0x100000", "0x1001"]) == Err(ErrorCode::EINVAL),
+        "more than a page",
+    )?;
+    check(
+        parse_peek_args(&["0x100000", "0"]) == Err(ErrorCode::EINVAL),
+        "zero length",
+    )?;
+    check(
+        parse_peek_args(&["0x100000"]) == Err(ErrorCode::EINVAL),
+        "missing length",
+    )
+}
+
+fn peek_range_validation(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    // low memory up to the EBDA and 1MiB to 128MiB, like QEMU's default map
+    let areas = [(0x0, 0x9_fbff), (0x10_0000, 0x7ff_ffff)];
+
+    check(range_in_areas(&areas, 0x10_0000, 16), "inside an area")?;
+    check(range_in_areas(&areas, 0x7ff_f000, 0x1000), "last frame")?;
+    check(!range_in_areas(&areas, 0xa_0000, 1), "hole")?;
+    check(
+        !range_in_areas(&areas, 0x9_f000, 0x2000),
+        "straddling the hole",
+    )?;
+    check(!range_in_areas(&areas, 0x800_0000, 1), "past the end")?;
+    check(!range_in_areas(&areas, 0x10_0000, 0), "empty range")?;
+    check(!range_in_areas(&areas, usize::MAX, 2), "overflowing range")
+}
+
+/// Output of running `line` in the shell
+fn run(line: &str) -> String {
+    let mut out = String::new();
+    execute(&mut out, line);
+    out
+}
+
+fn unknown_command(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(
+        run("frobnicate a b") == "frobnicate: command not found\n",
+        "unknown command",
+    )?;
+    check(
+        run("ECHO hi") == "ECHO: command not found\n",
+        "command names are case sensitive",
+    )?;
+    check(run("").is_empty(), "empty line printed something")?;
+    check(run(" \t ").is_empty(), "blank line printed something")
+}
+
+fn argument_splitting(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(
+        split_line("echo a b") == ["echo", "a", "b"],
+        "single spaces",
+    )?;
+    check(
+        split_line("  echo \t a   b  ") == ["echo", "a", "b"],
+        "runs of spaces and tabs",
+    )?;
+    check(split_line("").is_empty(), "empty line")?;
+    check(
+        run("echo  hello \t  world ") == "hello world\n",
+        "arguments weren't passed to the command one word each",
+    )?;
+    check(
+        run("cd a b") == "cd: EINVAL\n",
+        "too many arguments weren't rejected",
+    )
+}
+
+/// Creates the file at `path` with `contents`
+fn create_file(path: &str, contents: &[u8]) -> Result<(), &'static str> {
+    VFS.lock()
+        .create_file(Path::new(path))
+        .map_err(|_| "failed to create file")?;
+
+    let mut fd_table = FdTable::new();
+    let fd = fd_table
+        .open(Path::new(path))
+        .map_err(|_| "failed to open file")?;
+    let written = fd_table.write(fd, contents);
+    fd_table.close(fd).map_err(|_| "failed to close file")?;
+
+    check(written.is_ok(), "failed to write file")
+}
+
+fn cat_command(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    create_file("/ktest-cat.txt", b"first line\nsecond line\n")?;
+
+    check(
+        run("cat /ktest-cat.txt") == "first line\nsecond line\n",
+        "file contents",
+    )?;
+    check(
+        run("cat /ktest-cat-missing.txt") == "cat: ENOENT\n",
+        "missing file",
+    )?;
+    check(run("cat") == "cat: EINVAL\n", "missing path")?;
+    check(
+        run("cat /ktest-cat.txt /ktest-cat.txt") == "cat: EINVAL\n",
+        "more than one path",
+    )
+}
+
+fn ls_command(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    VFS.lock()
+        .create_dir_all(Path::new("/ktest-ls/sub"))
+        .map_err(|_| "failed to create directories")?;
+    create_file("/ktest-ls/b.txt", b"12345")?;
+    create_file("/ktest-ls/a.txt", b"")?;
+
+    check(
+        run("ls /ktest-ls") == "       0 a.txt\n       5 b.txt\n         sub/\n",
+        "entries sorted by name with sizes and a / on directories",
+    )?;
+    check(
+        run("ls /ktest-ls/b.txt") == "       5 b.txt\n",
+        "single file",
+    )?;
+    check(run("ls /ktest-ls/sub").is_empty(), "empty directory")?;
+    check(
+        run("ls /ktest-ls-missing") == "ls: ENOENT\n",
+        "missing directory",
+    )?;
+    check(run("ls / /") == "ls: EINVAL\n", "more than one path")
+}
+
+fn echo_command(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(run("echo hello world") == "hello world\n", "printing")?;
+    check(run("echo") == "\n", "no text")?;
+
+    check(
+        run("echo hello > /ktest-echo.txt").is_empty(),
+        "redirected text was printed",
+    )?;
+    check(
+        run("cat /ktest-echo.txt") == "hello\n",
+        "file wasn't created with the text",
+    )?;
+    check(
+        run("echo world >> /ktest-echo.txt").is_empty(),
+        "appended text was printed",
+    )?;
+    check(
+        run("cat /ktest-echo.txt") == "hello\nworld\n",
+        ">> didn't concatenate",
+    )?;
+
+    VFS.lock()
+        .create_dir_all(Path::new("/ktest-echo-dir"))
+        .map_err(|_| "failed to create directory")?;
+    check(
+        run("echo hi > /ktest-echo-dir") == "echo: EISDIR\n",
+        "writing to a directory",
+    )?;
+    check(run("echo hi >") == "echo: EINVAL\n", "missing path")
+}
+
+fn echo_truncate_append(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    run("echo a much longer first line > /ktest-echo-twice.txt");
+    run("echo short > /ktest-echo-twice.txt");
+    check(
+        run("cat /ktest-echo-twice.txt") == "short\n",
+        "> didn't truncate the old contents",
+    )?;
+
+    run("echo one >> /ktest-echo-twice.txt");
+    run("echo two >> /ktest-echo-twice.txt");
+    check(
+        run("cat /ktest-echo-twice.txt") == "short\none\ntwo\n",
+        ">> didn't append after the existing contents",
+    )
+}
+
+/// Ports and values `power::shutdown_with` writes for hypervisor `signature`
+fn shutdown_writes(signature: Option<&str>) -> Result<Vec<(u16, u16)>, &'static str> {
+    let mut writes = Vec::new();
+    let result = power::shutdown_with(signature, |port, value| writes.push((port, value)));
+
+    check(
+        result == Err(ErrorCode::ENOSYS),
+        "returned something but ENOSYS after not powering off",
+    )?;
+    Ok(writes)
+}
+
+fn shutdown_ports(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(
+        shutdown_writes(Some("TCGTCGTCGTCG"))? == [(0x604, 0x2000)],
+        "QEMU",
+    )?;
+    check(
+        shutdown_writes(Some("KVMKVMKVM\0\0\0"))? == [(0x604, 0x2000)],
+        "QEMU under KVM",
+    )?;
+    check(
+        shutdown_writes(Some("VBoxVBoxVBox"))? == [(0x4004, 0x3400)],
+        "VirtualBox",
+    )?;
+    check(
+        shutdown_writes(Some("Microsoft Hv"))?.is_empty(),
+        "wrote a port on an unknown hypervisor",
+    )?;
+    check(
+        shutdown_writes(None)?.is_empty(),
+        "wrote a port on bare metal",
+    )
+}
+
+fn peek_range_edges(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let areas = [(0x0, 0x9_fbff), (0x10_0000, 0x7ff_ffff)];
+
+    check(
+        !range_in_areas(&areas, 0x7ff_f000, 0x2000),
+        "straddling the end of the last area",
+    )?;
+    check(
+        !range_in_areas(&areas, 0x7ff_ffff, 2),
+        "one byte past the end of the last area",
+    )?;
+    check(
+        !range_in_areas(&areas, 0x9_f000, 0x6_2000),
+        "spanning the hole with both ends in an area",
+    )?;
+
+    // touching areas leave no hole in between
+    let touching = [(0x0, 0xfff), (0x1000, 0x1fff)];
+    check(
+        range_in_areas(&touching, 0x800, 0x1000),
+        "spanning two touching areas",
+    )
+}
@@ -0,0 +1,101 @@
+use crate::arch::cpu::Cpu;
+use crate::arch::mm::MemoryController;
+use crate::ktest::{check, Test};
+use crate::task::executor::Executor;
+use crate::task::Task;
+use core::future::poll_fn;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Poll, Waker};
+use spin::Mutex;
+use x86_64::instructions::interrupts;
+
+pub static TESTS: &[(&str, Test)] = &[
+    ("executor idles when empty", idle_when_empty),
+    ("executor doesn't idle with ready tasks", no_idle_when_ready),
+    ("wake from an interrupt handler", wake_from_interrupt),
+    ("task ids", task_ids),
+];
+
+fn idle_when_empty(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let executor = Executor::new();
+    check(executor.sleep_if_idle(), "empty executor didn't halt")?;
+    check(interrupts::are_enabled(), "interrupts are off after idling")
+}
+
+fn no_idle_when_ready(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let mut executor = Executor::new();
+    executor.spawn(Task::new(async {}));
+
+    check(
+        !executor.sleep_if_idle(),
+        "executor halted with a ready task",
+    )?;
+    check(
+        interrupts::are_enabled(),
+        "interrupts are off after finding a ready task",
+    )
+}
+
+fn wake_from_interrupt(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    static POLLS: AtomicUsize = AtomicUsize::new(0);
+    static WAKER: Mutex<Option<Waker>> = Mutex::new(None);
+
+    let mut executor = Executor::new();
+    executor.spawn(Task::new(poll_fn(|context| {
+        *WAKER.lock() = Some(context.waker().clone());
+        if POLLS.fetch_add(1, Ordering::SeqCst) == 1 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })));
+
+    executor.run_ready_tasks();
+    check(
+        POLLS.load(Ordering::SeqCst) == 1,
+        "spawned task wasn't polled",
+    )?;
+    executor.run_ready_tasks();
+    check(
+        POLLS.load(Ordering::SeqCst) == 1,
+        "task was polled again without a wakeup",
+    )?;
+
+    // what an interrupt handler does, e.g. the keyboard one
+    Cpu::without_interrupts(|| {
+        if let Some(waker) = WAKER.lock().as_ref() {
+            waker.wake_by_ref();
+        }
+    });
+    check(
+        !executor.sleep_if_idle(),
+        "executor idled with a woken task",
+    )?;
+    executor.run_ready_tasks();
+    check(
+        POLLS.load(Ordering::SeqCst) == 2,
+        "woken task wasn't polled on the next run",
+    )
+}
+
+fn task_ids(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let first = Task::new(async {});
+    let second = Task::new(async {});
+    let (first_id, second_id) = (first.id(), second.id());
+    check(first_id != second_id, "two tasks got the same id")?;
+    check(first_id < second_id, "ids aren't assigned in order")?;
+
+    let mut executor = Executor::new();
+    check(
+        executor.spawn(first) == first_id && executor.spawn(second) == second_id,
+        "spawn didn't return the task's id",
+    )?;
+    check(executor.task_count() == 2, "spawned tasks are missing")?;
+
+    // both finish on their first poll, so every queued id has to map to a task
+    executor.run_ready_tasks();
+    check(
+        executor.task_count() == 0,
+        "a queued id didn't refer to a spawned task",
+    )
+}
@@ -0,0 +1,167 @@
+use crate::arch::mm::MemoryController;
+use crate::ktest::{check, Test};
+use crate::kutils::possibly_uninit::PossiblyUninit;
+use crate::logging::vga::bufferwriter::{glyph_origin, grid_size, Buffer};
+use crate::logging::vga::textmodewriter::cell_byte;
+use crate::vga::{check_mapped, Pixel, VGAFramebuffer};
+use font8x8::{UnicodeFonts, BASIC_FONTS};
+
+pub static TESTS: &[(&str, Test)] = &[
+    ("font scale", font_scale),
+    ("non-ASCII glyphs", non_ascii_glyphs),
+    ("glyph columns", glyph_columns),
+    ("dirty text rows", dirty_rows),
+    ("framebuffer move_up", framebuffer_move_up),
+    ("framebuffer mapping check", framebuffer_mapping_check),
+    ("PossiblyUninit", possibly_uninit),
+];
+
+fn font_scale(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(
+        grid_size(768, 1024, 1) == (96, 128),
+        "8x8 glyphs on 1024x768",
+    )?;
+    check(
+        grid_size(768, 1024, 2) == (48, 64),
+        "16x16 glyphs on 1024x768",
+    )?;
+    // partial glyphs don't count
+    check(
+        grid_size(770, 1030, 3) == (32, 42),
+        "24x24 glyphs on 1030x770",
+    )
+}
+
+fn non_ascii_glyphs(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let replacement = BASIC_FONTS.get('?');
+    check(
+        Some(Buffer::get_bytearray_for_char('A')) == BASIC_FONTS.get('A'),
+        "ASCII glyph",
+    )?;
+    for ch in ['é', 'Ŋ', '😀'] {
+        check(
+            Some(Buffer::get_bytearray_for_char(ch)) == replacement,
+            "framebuffer didn't draw the replacement glyph",
+        )?;
+    }
+
+    check(cell_byte('A') == b'A', "ASCII cell")?;
+    check(
+        cell_byte('Ŋ') == 0xfe && cell_byte('😀') == 0xfe,
+        "text mode didn't write the replacement byte",
+    )
+}
+
+fn glyph_columns(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    check(glyph_origin(0, 0, 1) == (0, 0), "first cell isn't at x=0")?;
+    check(
+        glyph_origin(1, 2, 1) == (8, 16),
+        "cells aren't 8 pixels apart",
+    )?;
+
+    for (width, scale) in [(1024, 1), (1000, 2), (1030, 3)] {
+        let (_, cols) = grid_size(768, width, scale);
+        let (last_x, _) = glyph_origin(cols - 1, 0, scale);
+        // rightmost pixel of the rightmost glyph
+        check(
+            last_x + 8 * scale - 1 < width,
+            "last column draws past the screen edge",
+        )?;
+    }
+
+    Ok(())
+}
+
+fn dirty_rows(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let mut buffer = Buffer::new(4, 10, 1);
+    check(buffer.dirty_rows().next().is_none(), "new buffer is dirty")?;
+
+    buffer.write_string("x");
+    check(
+        buffer.dirty_rows().eq([0]),
+        "one character dirtied more than its row",
+    )?;
+
+    buffer.write_string("\ny");
+    check(
+        buffer.dirty_rows().eq([0, 1]),
+        "writing on the next row didn't dirty only that row",
+    )
+}
+
+/// 2x4 pixels, 32 bits per pixel
+const TEST_WIDTH: usize = 2;
+const TEST_HEIGHT: usize = 4;
+const TEST_PITCH: usize = TEST_WIDTH * 4;
+
+fn framebuffer_move_up(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let mut backing = vec![0u8; TEST_HEIGHT * TEST_PITCH];
+    // SAFETY: `backing` is mapped and outlives the framebuffer
+    let mut framebuffer = unsafe {
+        VGAFramebuffer::new(
+            backing.as_mut_ptr() as u64,
+            TEST_HEIGHT,
+            TEST_WIDTH,
+            TEST_PITCH,
+            32,
+        )
+    };
+
+    framebuffer.write_pixel(Pixel { r: 1, g: 2, b: 3 }, 1, 2);
+    framebuffer.write_pixel(Pixel { r: 4, g: 5, b: 6 }, 0, 3);
+    framebuffer.move_up(2);
+
+    let bytes = framebuffer.bytes();
+    check(
+        bytes[4..7] == [1, 2, 3] && bytes[TEST_PITCH..TEST_PITCH + 3] == [4, 5, 6],
+        "content didn't move up by two scanlines",
+    )?;
+    check(
+        bytes[2 * TEST_PITCH..].iter().all(|byte| *byte == 0),
+        "exposed scanlines weren't cleared",
+    )?;
+
+    framebuffer.move_up(TEST_HEIGHT + 1);
+    check(
+        framebuffer.bytes().iter().all(|byte| *byte == 0),
+        "moving up past the height didn't clear everything",
+    )
+}
+
+fn framebuffer_mapping_check(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    // only 0x1000-0x1fff is mapped
+    let translate = |addr: usize| (0x1000..0x2000).contains(&addr).then_some(addr);
+
+    check(
+        check_mapped(0x1000, 0x1000, translate) == Ok(()),
+        "mapped region was rejected",
+    )?;
+    check(
+        check_mapped(0x1800, 0x1000, translate) == Err(0x27ff),
+        "region running past the mapping was accepted",
+    )?;
+    check(
+        check_mapped(0x800, 0x1000, translate) == Err(0x800),
+        "region starting before the mapping was accepted",
+    )
+}
+
+fn possibly_uninit(_: &mut MemoryController<'_>) -> Result<(), &'static str> {
+    let mut init = PossiblyUninit::Init(7);
+    check(
+        init.is_init() && !init.is_uninit(),
+        "Init isn't initialized",
+    )?;
+    check(
+        *init.expect("missing") == 7,
+        "expect returned the wrong value",
+    )?;
+    *init.expect_mut("missing") = 8;
+    check(init == PossiblyUninit::Init(8), "expect_mut didn't write")?;
+
+    let uninit: PossiblyUninit<u32> = PossiblyUninit::Uninit;
+    check(
+        uninit.is_uninit() && !uninit.is_init(),
+        "Uninit is initialized",
+    )
+}
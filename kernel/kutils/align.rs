@@ -0,0 +1,38 @@
+use core::ops::{Add, BitAnd, Not, Sub};
+
+/// Unsigned integer types the alignment helpers work on
+pub trait Unsigned:
+    Copy + Eq + Add<Output = Self> + Sub<Output = Self> + BitAnd<Output = Self> + Not<Output = Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+}
+
+macro_rules! impl_unsigned {
+    ($($ty:ty),*) => {
+        $(
+            impl Unsigned for $ty {
+                const ZERO: Self = 0;
+                const ONE: Self = 1;
+            }
+        )*
+    };
+}
+
+impl_unsigned!(u8, u16, u32, u64, usize);
+
+/// Rounds `value` down to a multiple of `align`, which must be a power of two
+#[allow(unused)]
+pub fn align_down<T: Unsigned>(value: T, align: T) -> T {
+    value & !(align - T::ONE)
+}
+
+/// Rounds `value` up to a multiple of `align`, which must be a power of two
+pub fn align_up<T: Unsigned>(value: T, align: T) -> T {
+    align_down(value + (align - T::ONE), align)
+}
+
+/// Checks whether `value` is a multiple of `align`, which must be a power of two
+pub fn is_aligned<T: Unsigned>(value: T, align: T) -> bool {
+    value & (align - T::ONE) == T::ZERO
+}
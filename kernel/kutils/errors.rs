@@ -1,6 +1,6 @@
 #[allow(unused)]
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 #[repr(u8)]
 pub enum ErrorCode {
     /// Operation not permitted
@@ -262,3 +262,10 @@ pub enum ErrorCode {
     /// State not recoverable
     ENOTRECOVERABLE = 131,
 }
+
+/// Writing formatted output failed, e.g. from a shell command
+impl From<core::fmt::Error> for ErrorCode {
+    fn from(_: core::fmt::Error) -> Self {
+        ErrorCode::EIO
+    }
+}
@@ -0,0 +1,64 @@
+use core::fmt::{Display, Formatter};
+
+const BYTES_PER_LINE: usize = 16;
+
+/// One line of canonical hexdump output: the address, up to 16 bytes in hex and the same
+/// bytes as ASCII, e.g. `0000000000001000  48 65 6c 6c 6f 00 ...  |Hello.|`
+pub struct HexdumpLine<'a> {
+    addr: usize,
+    bytes: &'a [u8],
+}
+
+impl<'a> HexdumpLine<'a> {
+    pub fn new(addr: usize, bytes: &'a [u8]) -> Self {
+        assert!(bytes.len() <= BYTES_PER_LINE);
+
+        Self { addr, bytes }
+    }
+}
+
+impl Display for HexdumpLine<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:016x}  ", self.addr)?;
+
+        for idx in 0..BYTES_PER_LINE {
+            if idx == BYTES_PER_LINE / 2 {
+                write!(f, " ")?;
+            }
+
+            match self.bytes.get(idx) {
+                Some(byte) => write!(f, "{:02x} ", byte)?,
+                None => write!(f, "   ")?,
+            }
+        }
+
+        write!(f, " |")?;
+        for &byte in self.bytes {
+            let ch = if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            };
+            write!(f, "{}", ch)?;
+        }
+        write!(f, "|")
+    }
+}
+
+/// Prints `len` bytes starting at `addr` to serial, 16 bytes per line
+///
+/// # SAFETY
+/// The whole range must be mapped and readable
+pub unsafe fn hexdump(addr: *const u8, len: usize) {
+    hexdump_bytes(addr as usize, core::slice::from_raw_parts(addr, len));
+}
+
+/// Prints `bytes` to serial as if they were found at `addr`, 16 bytes per line
+pub fn hexdump_bytes(addr: usize, bytes: &[u8]) {
+    for (line_idx, line) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        serial_println!(
+            "{}",
+            HexdumpLine::new(addr + line_idx * BYTES_PER_LINE, line)
+        );
+    }
+}
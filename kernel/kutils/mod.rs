@@ -1,4 +1,8 @@
+pub mod align;
 pub mod errors;
+pub mod hexdump;
 pub mod multiboot;
 pub mod possibly_uninit;
+pub mod qemu;
+pub mod units;
 pub mod unsafe_sync;
@@ -24,6 +24,33 @@ pub fn get_kernel_start_end(boot_info: &BootInformation) -> (usize, usize) {
     (kernel_start as usize, kernel_end as usize)
 }
 
+/// Physical start and inclusive end of the first boot module, which holds the initrd
+pub fn get_initrd_start_end(boot_info: &BootInformation) -> Option<(usize, usize)> {
+    let module = boot_info.module_tags().next()?;
+    if module.end_address() <= module.start_address() {
+        return None;
+    }
+
+    Some((
+        module.start_address() as usize,
+        module.end_address() as usize - 1,
+    ))
+}
+
+/// Whether the kernel command line contains `flag` as a whitespace separated word
+pub fn has_cmdline_flag(boot_info: &BootInformation, flag: &str) -> bool {
+    boot_info
+        .command_line_tag()
+        .and_then(|tag| tag.command_line().ok())
+        .map_or(false, |cmdline| cmdline_has_word(cmdline, flag))
+}
+
+/// Whether `cmdline` contains `word` as a whitespace separated word, so `nofb` isn't
+/// found in `nofbx`
+pub fn cmdline_has_word(cmdline: &str, word: &str) -> bool {
+    cmdline.split_whitespace().any(|w| w == word)
+}
+
 pub fn get_multiboot_info_start_end(boot_info: &BootInformation) -> (usize, usize) {
     let multiboot_start = boot_info.start_address();
     let multiboot_end = boot_info.start_address() + boot_info.total_size() - 1;
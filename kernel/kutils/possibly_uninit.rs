@@ -22,6 +22,22 @@ impl<T> PossiblyUninit<T> {
         }
     }
 
+    /// Like [`PossiblyUninit::unwrap_ref`], but panics with `msg`
+    pub fn expect(&self, msg: &str) -> &T {
+        match self {
+            PossiblyUninit::Init(v) => v,
+            PossiblyUninit::Uninit => panic!("{}", msg),
+        }
+    }
+
+    /// Like [`PossiblyUninit::unwrap_ref_mut`], but panics with `msg`
+    pub fn expect_mut(&mut self, msg: &str) -> &mut T {
+        match self {
+            PossiblyUninit::Init(v) => v,
+            PossiblyUninit::Uninit => panic!("{}", msg),
+        }
+    }
+
     pub fn unwrap(self) -> T {
         match self {
             PossiblyUninit::Init(v) => v,
@@ -0,0 +1,24 @@
+use x86_64::instructions::port::Port;
+
+/// I/O port of QEMU's `isa-debug-exit` device, see `b.py test`
+const ISA_DEBUG_EXIT_PORT: u16 = 0xf4;
+
+/// QEMU exits with `(code << 1) | 1`, so neither of these can be confused with QEMU's own
+/// exit codes 0 and 1
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(u32)]
+pub enum ExitCode {
+    Success = 0x10,
+    Failed = 0x11,
+}
+
+/// Exits QEMU with `code`. Halts if the exit device isn't there, e.g. on real hardware.
+#[allow(unused)]
+pub fn qemu_exit(code: ExitCode) -> ! {
+    unsafe {
+        Port::<u32>::new(ISA_DEBUG_EXIT_PORT).write(code as u32);
+    }
+
+    crate::arch::cpu::Cpu::halt();
+}
@@ -0,0 +1,61 @@
+use core::fmt::{Display, Formatter};
+
+#[allow(non_upper_case_globals)]
+pub const KiB: usize = 1024;
+#[allow(non_upper_case_globals)]
+pub const MiB: usize = 1024 * KiB;
+#[allow(non_upper_case_globals)]
+pub const GiB: usize = 1024 * MiB;
+
+/// Byte count that displays in the largest fitting binary unit, e.g. `16.00 MiB`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct HumanBytes(usize);
+
+pub fn format_bytes(n: usize) -> HumanBytes {
+    HumanBytes(n)
+}
+
+impl Display for HumanBytes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let bytes = self.0;
+        let (unit, suffix) = if bytes >= GiB {
+            (GiB, "GiB")
+        } else if bytes >= MiB {
+            (MiB, "MiB")
+        } else if bytes >= KiB {
+            (KiB, "KiB")
+        } else {
+            return write!(f, "{} B", bytes);
+        };
+
+        // integer math only, the kernel doesn't touch the FPU
+        let whole = bytes / unit;
+        let hundredths = (bytes % unit) * 100 / unit;
+        write!(f, "{}.{:02} {}", whole, hundredths, suffix)
+    }
+}
+
+/// Millisecond count that displays as hours, minutes and seconds, e.g. `1h 01m 01.500s`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct HumanDuration(u64);
+
+pub fn format_duration(ms: u64) -> HumanDuration {
+    HumanDuration(ms)
+}
+
+impl Display for HumanDuration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let ms = self.0;
+        let hours = ms / 3_600_000;
+        let minutes = ms / 60_000 % 60;
+        let seconds = ms / 1000 % 60;
+        write!(
+            f,
+            "{}h {:02}m {:02}.{:03}s",
+            hours,
+            minutes,
+            seconds,
+            ms % 1000
+        )
+    }
+}
@@ -4,6 +4,60 @@ pub mod vga;
 #[macro_use]
 pub mod serial;
 
+use crate::arch::interrupts::pit8254::get_global_ms;
+use core::fmt::{Arguments, Display, Formatter};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// Severity of a log message, from least to most severe
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+#[repr(u8)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+/// Messages less severe than `level` get dropped
+#[allow(unused)]
+pub fn set_log_level(level: Level) {
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+#[doc(hidden)]
+pub fn log_enabled(level: Level) -> bool {
+    level as u8 >= LOG_LEVEL.load(Ordering::Relaxed)
+}
+
+static TIMESTAMPS: AtomicBool = AtomicBool::new(false);
+
+/// Prefixes log messages with the time since boot, like `[   12.345]`. Off by default.
+#[allow(unused)]
+pub fn set_timestamps(enabled: bool) {
+    TIMESTAMPS.store(enabled, Ordering::Relaxed);
+}
+
+/// Prefix for a log message, empty unless timestamps are enabled
+#[doc(hidden)]
+pub struct Timestamp(pub Option<u64>);
+
+#[doc(hidden)]
+pub fn timestamp() -> Timestamp {
+    Timestamp(TIMESTAMPS.load(Ordering::Relaxed).then(get_global_ms))
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self.0 {
+            Some(ms) => write!(f, "[{:>5}.{:03}] ", ms / 1000, ms % 1000),
+            None => Ok(()),
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! println {
     () => (print!("\n"));
@@ -16,8 +70,9 @@ macro_rules! print {
     ($prefix_colored:literal, $prefix:literal, $($arg:tt)*) => {
     #[allow(clippy::redundant_closure_call)]
         (|msg: core::fmt::Arguments| {
-            serial_print!("{} {}", $prefix_colored, msg);
-            vprint!("{} {}", $prefix, msg);
+            let timestamp = $crate::logging::timestamp();
+            serial_print!("{}{} {}", timestamp, $prefix_colored, msg);
+            vprint!("{}{} {}", timestamp, $prefix, msg);
         })(format_args!($($arg)*))
     }
 }
@@ -35,24 +90,57 @@ macro_rules! print_raw {
 
 #[macro_export]
 macro_rules! info {
-    ($fmt:expr) => (print!("\x1B[1;34m[ Info  ]\x1B[0m", "[ Info  ]", concat!($fmt, "\n")));
-    ($fmt:expr, $($arg:tt)*) => (print!("\x1B[1;34m[ Info  ]\x1B[0m", "[ Info  ]", concat!($fmt, "\n"), $($arg)*));
+    ($fmt:expr) => (if $crate::logging::log_enabled($crate::logging::Level::Info) { print!("\x1B[1;34m[ Info  ]\x1B[0m", "[ Info  ]", concat!($fmt, "\n")) });
+    ($fmt:expr, $($arg:tt)*) => (if $crate::logging::log_enabled($crate::logging::Level::Info) { print!("\x1B[1;34m[ Info  ]\x1B[0m", "[ Info  ]", concat!($fmt, "\n"), $($arg)*) });
 }
 
 #[macro_export]
 macro_rules! debug {
-    ($fmt:expr) => (#[cfg(debug_assertions)] print!("\x1B[1;32m[ Debug ]\x1B[0m", "[ Debug ]", concat!($fmt, "\n")));
-    ($fmt:expr, $($arg:tt)*) => (#[cfg(debug_assertions)] print!("\x1B[1;32m[ Debug ]\x1B[0m", "[ Debug ]", concat!($fmt, "\n"), $($arg)*));
+    ($fmt:expr) => (#[cfg(debug_assertions)] if $crate::logging::log_enabled($crate::logging::Level::Debug) { print!("\x1B[1;32m[ Debug ]\x1B[0m", "[ Debug ]", concat!($fmt, "\n")) });
+    ($fmt:expr, $($arg:tt)*) => (#[cfg(debug_assertions)] if $crate::logging::log_enabled($crate::logging::Level::Debug) { print!("\x1B[1;32m[ Debug ]\x1B[0m", "[ Debug ]", concat!($fmt, "\n"), $($arg)*) });
 }
 
 #[macro_export]
 macro_rules! error {
-    ($fmt:expr) => (print!("\x1B[1;31m[ Error ]\x1B[0m", "[ Error ]", concat!($fmt, "\n")));
-    ($fmt:expr, $($arg:tt)*) => (print!("\x1B[1;31m[ Error ]\x1B[0m", "[ Error ]", concat!($fmt, "\n"), $($arg)*));
+    ($fmt:expr) => (if $crate::logging::log_enabled($crate::logging::Level::Error) { print!("\x1B[1;31m[ Error ]\x1B[0m", "[ Error ]", concat!($fmt, "\n")) });
+    ($fmt:expr, $($arg:tt)*) => (if $crate::logging::log_enabled($crate::logging::Level::Error) { print!("\x1B[1;31m[ Error ]\x1B[0m", "[ Error ]", concat!($fmt, "\n"), $($arg)*) });
 }
 
 #[macro_export]
 macro_rules! warn {
-    ($fmt:expr) => (print!("\x1B[1;33m[ Warn  ]\x1B[0m", "[ Warn  ]", concat!($fmt, "\n")));
-    ($fmt:expr, $($arg:tt)*) => (print!("\x1B[1;33m[ Warn  ]\x1B[0m", "[ Warn  ]", concat!($fmt, "\n"), $($arg)*));
+    ($fmt:expr) => (if $crate::logging::log_enabled($crate::logging::Level::Warn) { print!("\x1B[1;33m[ Warn  ]\x1B[0m", "[ Warn  ]", concat!($fmt, "\n")) });
+    ($fmt:expr, $($arg:tt)*) => (if $crate::logging::log_enabled($crate::logging::Level::Warn) { print!("\x1B[1;33m[ Warn  ]\x1B[0m", "[ Warn  ]", concat!($fmt, "\n"), $($arg)*) });
+}
+
+/// Like `assert!`, but logs the failed condition as an error before panicking, so it makes
+/// it into the log even if the panic handler can't print.
+///
+/// `kassert!(@fail handler; ...)` hands the message to `handler` instead of
+/// [`assertion_failed`](crate::logging::assertion_failed).
+#[macro_export]
+macro_rules! kassert {
+    (@fail $fail:path; $cond:expr $(,)?) => {
+        if !$cond {
+            $fail(format_args!("Assertion failed: {}", stringify!($cond)));
+        }
+    };
+    (@fail $fail:path; $cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            $fail(format_args!("Assertion failed: {}: {}", stringify!($cond), format_args!($($arg)+)));
+        }
+    };
+    ($cond:expr $(,)?) => {
+        $crate::kassert!(@fail $crate::logging::assertion_failed; $cond)
+    };
+    ($cond:expr, $($arg:tt)+) => {
+        $crate::kassert!(@fail $crate::logging::assertion_failed; $cond, $($arg)+)
+    };
+}
+
+/// Logs a failed `kassert!` as an error, then panics with the same message
+#[doc(hidden)]
+#[track_caller]
+pub fn assertion_failed(message: Arguments) -> ! {
+    error!("{}", message);
+    panic!("{}", message);
 }
@@ -1,26 +1,194 @@
 use crate::arch::cpu::Cpu;
+use crate::kutils::errors::ErrorCode;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicU16, Ordering};
 use lazy_static::lazy_static;
 use spin::Mutex;
 use uart_16550::SerialPort;
+use x86_64::instructions::port::Port;
+
+/// Clock of the UART divided by 16, the baud rate at a divisor of 1
+const MAX_BAUD: u32 = 115200;
+const DEFAULT_BAUD: u32 = MAX_BAUD;
+
+/// Byte sent through the UART in loopback mode to check that it's there
+const SELF_TEST_BYTE: u8 = 0xAE;
+
+/// Line control: divisor latch access bit
+const LCR_DLAB: u8 = 0x80;
+/// Line control: 8 data bits, no parity, 1 stop bit
+const LCR_8N1: u8 = 0x03;
+/// FIFO control: enable and clear both FIFOs, interrupt at 14 bytes
+const FCR_ENABLE: u8 = 0xC7;
+/// Interrupt enable: received data available
+const IER_RECEIVED: u8 = 0x01;
+/// Line status: transmit holding register empty
+const LSR_TRANSMIT_EMPTY: u8 = 1 << 5;
+/// Line status reads before `PanicSerial` writes a byte anyway
+const TRANSMIT_POLL_LIMIT: usize = 100_000;
+/// Modem control: RTS, OUT1, OUT2 and loopback
+const MCR_LOOPBACK: u8 = 0x1E;
+/// Modem control: DTR, RTS and OUT2
+const MCR_NORMAL: u8 = 0x0B;
+
+#[allow(unused)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ComPort {
+    Com1,
+    Com2,
+    Com3,
+    Com4,
+}
+
+impl ComPort {
+    pub fn base(self) -> u16 {
+        match self {
+            ComPort::Com1 => 0x3F8,
+            ComPort::Com2 => 0x2F8,
+            ComPort::Com3 => 0x3E8,
+            ComPort::Com4 => 0x2E8,
+        }
+    }
+}
+
+/// Whether `configure` found a working UART. Serial printing is a no-op until it does.
+static UART_PRESENT: AtomicBool = AtomicBool::new(false);
+/// I/O base of the port `QEMU_SERIAL` writes to
+static UART_BASE: AtomicU16 = AtomicU16::new(0x3F8);
 
 lazy_static! {
-    pub static ref QEMU_SERIAL: Mutex<SerialPort> = {
-        let mut serial_port = unsafe { SerialPort::new(0x3F8) };
-        serial_port.init();
-        Mutex::new(serial_port)
-    };
+    pub static ref QEMU_SERIAL: Mutex<SerialPort> =
+        Mutex::new(unsafe { SerialPort::new(ComPort::Com1.base()) });
+}
+
+/// Sets up COM1 at 115200 baud if it's there
+pub fn init() {
+    let _ = configure(ComPort::Com1, DEFAULT_BAUD);
+}
+
+/// Switches serial output to `port` at `baud`, 8N1 with FIFOs enabled.
+/// Fails with `EINVAL` for a baud rate the UART can't produce and `ENODEV` if the port
+/// fails the loopback self-test, leaving the current port in use.
+pub fn configure(port: ComPort, baud: u32) -> Result<(), ErrorCode> {
+    let divisor = divisor_for(baud).ok_or(ErrorCode::EINVAL)?;
+    let base = port.base();
+
+    Cpu::without_interrupts(|| {
+        let mut serial = QEMU_SERIAL.lock();
+
+        // Without a UART the transmit holding register never empties, so printing would spin forever
+        if !unsafe { self_test(base) } {
+            return Err(ErrorCode::ENODEV);
+        }
+
+        unsafe {
+            program(base, divisor);
+            *serial = SerialPort::new(base);
+        }
+        UART_BASE.store(base, Ordering::SeqCst);
+        UART_PRESENT.store(true, Ordering::SeqCst);
+
+        Ok(())
+    })
+}
+
+/// Divisor latch value for `baud`, if it divides the UART clock evenly
+pub fn divisor_for(baud: u32) -> Option<u16> {
+    if baud == 0 || baud > MAX_BAUD || MAX_BAUD % baud != 0 {
+        return None;
+    }
+
+    u16::try_from(MAX_BAUD / baud).ok()
+}
+
+/// # SAFETY
+/// `base` must be the I/O base of a serial port
+unsafe fn program(base: u16, divisor: u16) {
+    let mut data = Port::<u8>::new(base);
+    let mut interrupt_enable = Port::<u8>::new(base + 1);
+    let mut fifo_control = Port::<u8>::new(base + 2);
+    let mut line_control = Port::<u8>::new(base + 3);
+    let mut modem_control = Port::<u8>::new(base + 4);
+
+    interrupt_enable.write(0);
+
+    // with DLAB set the data and interrupt enable registers hold the divisor
+    line_control.write(LCR_DLAB);
+    let [low, high] = divisor.to_le_bytes();
+    data.write(low);
+    interrupt_enable.write(high);
+
+    line_control.write(LCR_8N1);
+    fifo_control.write(FCR_ENABLE);
+    modem_control.write(MCR_NORMAL);
+    interrupt_enable.write(IER_RECEIVED);
+}
+
+/// # SAFETY
+/// `base` must be the I/O base of a serial port, or unused
+unsafe fn self_test(base: u16) -> bool {
+    let mut data = Port::<u8>::new(base);
+    let mut modem_control = Port::<u8>::new(base + 4);
+
+    modem_control.write(MCR_LOOPBACK);
+    data.write(SELF_TEST_BYTE);
+    let received = data.read();
+    modem_control.write(MCR_NORMAL);
+
+    passes_self_test(received)
+}
+
+/// Whether the byte read back in loopback mode is the one that was sent
+pub fn passes_self_test(received: u8) -> bool {
+    received == SELF_TEST_BYTE
 }
 
 #[doc(hidden)]
 pub fn print(args: core::fmt::Arguments) {
-    use core::fmt::Write;
+    if !UART_PRESENT.load(Ordering::Relaxed) {
+        return;
+    }
 
-    Cpu::without_interrupts(|| {
-        QEMU_SERIAL
-            .lock()
-            .write_fmt(args)
-            .expect("Printing to serial failed");
-    });
+    Cpu::without_interrupts(|| QEMU_SERIAL.lock().write_fmt(args))
+        .expect("Printing to serial failed");
+}
+
+/// Writes straight to the UART registers, without taking the `QEMU_SERIAL` lock or
+/// allocating. Only meant for panic and fault paths, where the lock holder may never
+/// release it. Output can interleave with a `serial_print!` that got interrupted.
+pub struct PanicSerial;
+
+impl Write for PanicSerial {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        if !UART_PRESENT.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let base = UART_BASE.load(Ordering::Relaxed);
+        let mut data = Port::<u8>::new(base);
+        let mut line_status = Port::<u8>::new(base + 5);
+
+        for byte in s.bytes() {
+            // a wedged UART shouldn't keep a panic from halting, give up waiting eventually
+            for _ in 0..TRANSMIT_POLL_LIMIT {
+                if is_transmit_ready(unsafe { line_status.read() }) {
+                    break;
+                }
+                Cpu::spin_loop_hint();
+            }
+
+            unsafe {
+                data.write(byte);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether the line status register says the UART can take another byte
+pub fn is_transmit_ready(line_status: u8) -> bool {
+    line_status & LSR_TRANSMIT_EMPTY != 0
 }
 
 /// Prints to the host through the serial interface.
@@ -39,3 +207,21 @@ macro_rules! serial_println {
     ($fmt:expr, $($arg:tt)*) => ($crate::serial_print!(
         concat!($fmt, "\n"), $($arg)*));
 }
+
+/// Prints a test result to the host through the serial interface only, prefixed with `[TEST] `
+/// so the host side can pick it out of the boot log.
+#[macro_export]
+macro_rules! test_print {
+    ($($arg:tt)*) => {
+        $crate::logging::serial::print(format_args!("[TEST] {}", format_args!($($arg)*)))
+    };
+}
+
+/// Prints a test result to the host through the serial interface only, appending a newline.
+#[macro_export]
+macro_rules! test_println {
+    () => ($crate::test_print!("\n"));
+    ($fmt:expr) => ($crate::test_print!(concat!($fmt, "\n")));
+    ($fmt:expr, $($arg:tt)*) => ($crate::test_print!(
+        concat!($fmt, "\n"), $($arg)*));
+}
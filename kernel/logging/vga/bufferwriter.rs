@@ -1,5 +1,6 @@
 use crate::vga::{Pixel, VGA_DRAWER};
 use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, Ordering};
 use font8x8::{UnicodeFonts, BASIC_FONTS};
 
 const FONT_WIDTH: usize = 8;
@@ -10,6 +11,15 @@ const PIXEL: Pixel = Pixel {
     b: 255,
 };
 const SPACE_CHAR: [u8; 8] = [0u8; FONT_HEIGHT];
+/// Drawn for characters the font has no glyph for
+const REPLACEMENT_CHAR: char = '?';
+
+const DRAWER_UNINIT: &str = "BufferWriter used before VGADrawer::init";
+
+static REPLACEMENT_LOGGED: AtomicBool = AtomicBool::new(false);
+
+/// Each glyph pixel is drawn as a `DEFAULT_FONT_SCALE`x`DEFAULT_FONT_SCALE` block
+pub const DEFAULT_FONT_SCALE: usize = 1;
 
 pub struct Buffer {
     buffer: Vec<Vec<[u8; 8]>>,
@@ -17,20 +27,25 @@ pub struct Buffer {
     rows: usize,
     cur_row: usize,
     cur_col: usize,
+    scale: usize,
+    /// Rows changed since the last commit, only these get redrawn
+    dirty_rows: Vec<bool>,
 }
 
 impl Buffer {
-    pub fn new(rows: usize, cols: usize) -> Self {
+    pub fn new(rows: usize, cols: usize, scale: usize) -> Self {
         Self {
             buffer: vec![vec![SPACE_CHAR; cols]; rows],
             cols,
             rows,
             cur_row: 0,
             cur_col: 0,
+            scale,
+            dirty_rows: vec![false; rows],
         }
     }
 
-    fn write_string(&mut self, s: &str) {
+    pub fn write_string(&mut self, s: &str) {
         for ch in s.chars() {
             match ch {
                 '\n' => {
@@ -41,16 +56,36 @@ impl Buffer {
         }
     }
 
-    fn get_bytearray_for_char(ch: char) -> [u8; 8] {
+    /// Rows written to since the last commit
+    #[allow(unused)]
+    pub fn dirty_rows(&self) -> impl Iterator<Item = usize> + '_ {
+        self.dirty_rows
+            .iter()
+            .enumerate()
+            .filter_map(|(row, dirty)| dirty.then_some(row))
+    }
+
+    /// Glyph drawn for `ch`, the one for `REPLACEMENT_CHAR` if the font has none
+    pub fn get_bytearray_for_char(ch: char) -> [u8; 8] {
         if let Some(byte_array) = BASIC_FONTS.get(ch) {
             byte_array
         } else {
-            SPACE_CHAR
+            if !REPLACEMENT_LOGGED.swap(true, Ordering::Relaxed) {
+                // straight to serial, the vga writer is locked while we're in here
+                crate::serial_println!(
+                    "No glyph for {:?}, drawing {:?} instead. Further substitutions are not logged.",
+                    ch,
+                    REPLACEMENT_CHAR
+                );
+            }
+
+            BASIC_FONTS.get(REPLACEMENT_CHAR).unwrap_or(SPACE_CHAR)
         }
     }
 
     fn write_byte(&mut self, ch: char) {
         self.buffer[self.cur_row][self.cur_col] = Self::get_bytearray_for_char(ch);
+        self.dirty_rows[self.cur_row] = true;
 
         self.cur_col += 1;
         if self.cur_col >= self.cols {
@@ -60,18 +95,25 @@ impl Buffer {
 
     fn draw_bitmap(&self, bitmap: &[u8; 8], col: usize, row: usize) {
         let mut drawer_binding = VGA_DRAWER.lock();
-        let drawer = &mut drawer_binding.buffer;
+        let drawer = &mut drawer_binding.expect_mut(DRAWER_UNINIT).buffer;
 
-        let mut y_pos = row * 8;
-        for scanline in bitmap {
+        let (glyph_x, glyph_y) = glyph_origin(col, row, self.scale);
+        for (scanline_idx, scanline) in bitmap.iter().enumerate() {
             for bit_idx in 0..FONT_WIDTH {
                 let bit = scanline >> bit_idx & 1;
 
                 if bit == 1 {
-                    drawer.write_pixel(PIXEL, col * 8 + bit_idx + 1, y_pos);
+                    for dy in 0..self.scale {
+                        for dx in 0..self.scale {
+                            drawer.write_pixel(
+                                PIXEL,
+                                glyph_x + bit_idx * self.scale + dx,
+                                glyph_y + scanline_idx * self.scale + dy,
+                            );
+                        }
+                    }
                 }
             }
-            y_pos += 1;
         }
     }
 
@@ -79,6 +121,7 @@ impl Buffer {
         for col in 0..self.cols {
             self.buffer[row][col] = SPACE_CHAR;
         }
+        self.dirty_rows[row] = true;
     }
 
     fn new_line(&mut self) {
@@ -89,21 +132,43 @@ impl Buffer {
                 }
             }
 
+            // the pixels move along with the text, so rows waiting for a redraw stay in step
+            VGA_DRAWER
+                .lock()
+                .expect_mut(DRAWER_UNINIT)
+                .buffer
+                .move_up(FONT_HEIGHT * self.scale);
+            self.dirty_rows.remove(0);
+            self.dirty_rows.push(false);
+
             self.cur_row = self.rows - 1;
             self.clear_row(self.cur_row);
-            VGA_DRAWER.lock().buffer.clear();
-            self.commit();
         } else {
             self.cur_row += 1;
         }
         self.cur_col = 0;
     }
 
-    fn commit(&self) {
-        for (row_idx, row) in self.buffer.iter().enumerate() {
-            for (col_idx, col) in row.iter().enumerate() {
+    /// Redraws the rows written to since the last commit
+    fn commit(&mut self) {
+        for row_idx in 0..self.rows {
+            if !self.dirty_rows[row_idx] {
+                continue;
+            }
+
+            let row_height = FONT_HEIGHT * self.scale;
+            {
+                let mut drawer_binding = VGA_DRAWER.lock();
+                let drawer = &mut drawer_binding.expect_mut(DRAWER_UNINIT).buffer;
+                for pos_y in row_idx * row_height..(row_idx + 1) * row_height {
+                    drawer.clear_y(pos_y);
+                }
+            }
+
+            for (col_idx, col) in self.buffer[row_idx].iter().enumerate() {
                 self.draw_bitmap(col, col_idx, row_idx);
             }
+            self.dirty_rows[row_idx] = false;
         }
     }
 }
@@ -113,16 +178,28 @@ pub struct BufferWriter {
 }
 
 impl BufferWriter {
-    pub fn new(height: usize, width: usize) -> Self {
-        let cols: usize = width / FONT_WIDTH;
-        let rows: usize = height / FONT_HEIGHT;
+    /// Fits as many `scale`d glyphs as the screen has room for
+    pub fn new(height: usize, width: usize, scale: usize) -> Self {
+        let (rows, cols) = grid_size(height, width, scale);
 
         Self {
-            buffer: Buffer::new(rows, cols),
+            buffer: Buffer::new(rows, cols, scale),
         }
     }
 }
 
+/// Top left pixel of the glyph cell at `col`, `row`
+pub fn glyph_origin(col: usize, row: usize, scale: usize) -> (usize, usize) {
+    (col * FONT_WIDTH * scale, row * FONT_HEIGHT * scale)
+}
+
+/// Rows and columns of `scale`d glyphs that fit on a `width`x`height` screen
+pub fn grid_size(height: usize, width: usize, scale: usize) -> (usize, usize) {
+    assert!(scale > 0, "font scale must be at least 1");
+
+    (height / (FONT_HEIGHT * scale), width / (FONT_WIDTH * scale))
+}
+
 impl Write for BufferWriter {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
         self.buffer.write_string(s);
@@ -1,10 +1,10 @@
 #[forbid(unsafe_code)]
-mod bufferwriter;
-mod textmodewriter;
+pub mod bufferwriter;
+pub mod textmodewriter;
 
 use crate::arch::cpu::Cpu;
 use crate::kutils::possibly_uninit::PossiblyUninit;
-use crate::logging::vga::bufferwriter::BufferWriter;
+use crate::logging::vga::bufferwriter::{BufferWriter, DEFAULT_FONT_SCALE};
 use crate::logging::vga::textmodewriter::TextModeWriter;
 use crate::VGA_DRAWER;
 use core::fmt::Write;
@@ -27,29 +27,34 @@ impl TextWriter {
         }
     }
 
+    /// Picks a writer based on the framebuffer the bootloader gave us.
+    ///
+    /// Without a framebuffer tag the bootloader left us in legacy VGA text mode. Indexed
+    /// colors aren't supported, so the writer stays uninitialized and output only goes to the
+    /// serial port. `nofb` skips this entirely.
     pub fn init(boot_info: &BootInformation) {
-        if let Some(framebuffer_tag) = boot_info.framebuffer_tag() {
-            if let FramebufferType::RGB { .. } = framebuffer_tag.buffer_type {
-                *WRITER.lock() = Self {
-                    writer: Writer::FrameBuffer(BufferWriter::new(
-                        framebuffer_tag.height as usize,
-                        framebuffer_tag.width as usize,
-                    )),
-                };
-
-                return;
-            }
-        }
-
-        *WRITER.lock() = Self {
-            writer: Writer::TextMode(unsafe { TextModeWriter::init() }),
+        let writer = match boot_info.framebuffer_tag() {
+            Some(framebuffer_tag) => match framebuffer_tag.buffer_type {
+                FramebufferType::RGB { .. } => Writer::FrameBuffer(BufferWriter::new(
+                    framebuffer_tag.height as usize,
+                    framebuffer_tag.width as usize,
+                    DEFAULT_FONT_SCALE,
+                )),
+                FramebufferType::Text => Writer::TextMode(unsafe { TextModeWriter::init() }),
+                FramebufferType::Indexed { .. } => Writer::Uninitialized,
+            },
+            None => Writer::TextMode(unsafe { TextModeWriter::init() }),
         };
+
+        *WRITER.lock() = Self { writer };
     }
 }
 
 impl Write for TextWriter {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
         match &mut self.writer {
+            // nothing to draw on yet, this still gets printed to serial
+            Writer::FrameBuffer(_) if VGA_DRAWER.lock().is_uninit() => Ok(()),
             Writer::FrameBuffer(writer) => writer.write_str(s),
             Writer::TextMode(writer) => writer.write_str(s),
             _ => Ok(()), // Will get printed to serial output instead
@@ -66,12 +71,15 @@ enum Writer {
 #[doc(hidden)]
 pub fn print(args: core::fmt::Arguments) {
     Cpu::without_interrupts(|| {
-        WRITER.lock().write_fmt(args).unwrap();
+        let result = WRITER.lock().write_fmt(args);
         let mut drawer_binding = VGA_DRAWER.lock();
         if let PossiblyUninit::Init(drawer) = &mut *drawer_binding {
             drawer.buffer.commit();
         }
-    });
+
+        result
+    })
+    .unwrap();
 }
 
 #[macro_export]
@@ -2,6 +2,8 @@ use core::fmt::Write;
 
 const BUFFER_HEIGHT: usize = 25;
 const BUFFER_WIDTH: usize = 80;
+/// `■` in code page 437, written for anything outside printable ASCII
+const REPLACEMENT_BYTE: u8 = 0xfe;
 
 /// The standard color palette in VGA text mode.
 #[allow(dead_code)]
@@ -78,12 +80,9 @@ impl TextModeWriter {
 
     fn write_string(&mut self, s: &str) {
         for ch in s.chars() {
-            let ch = ch as u8;
-
             match ch {
-                b'\n' => self.new_line(),
-                0x20..=0x7e => self.write_byte(ch),
-                _ => self.write_byte(0xfe),
+                '\n' => self.new_line(),
+                _ => self.write_byte(cell_byte(ch)),
             }
         }
     }
@@ -129,3 +128,12 @@ impl Write for TextModeWriter {
         Ok(())
     }
 }
+
+/// Byte written to a cell for `ch`. Matches on the char itself, `as u8` would turn e.g. 'Ŋ'
+/// (U+014A) into 'J'.
+pub fn cell_byte(ch: char) -> u8 {
+    match ch {
+        ' '..='~' => ch as u8,
+        _ => REPLACEMENT_BYTE,
+    }
+}
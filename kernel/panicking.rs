@@ -1,20 +1,169 @@
+use crate::arch::cpu::registers::Registers;
 use crate::arch::cpu::Cpu;
+use crate::kutils::possibly_uninit::PossiblyUninit;
+use crate::logging::serial::PanicSerial;
+use crate::vga::{Pixel, VGAFramebuffer, VGA_DRAWER};
+use core::fmt::Write;
 use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+use font8x8::{UnicodeFonts, BASIC_FONTS};
+
+const GLYPH_SIZE: usize = 8;
+const BANNER_BACKGROUND: Pixel = Pixel { r: 170, g: 0, b: 0 };
+const BANNER_FOREGROUND: Pixel = Pixel {
+    r: 255,
+    g: 255,
+    b: 255,
+};
+
+/// Set by the first panic. A panic while handling it takes the minimal path, as the first
+/// one may have happened with the logging or framebuffer locks held.
+static IN_PANIC: AtomicBool = AtomicBool::new(false);
 
 #[panic_handler]
 pub fn panic_handler(panic_info: &PanicInfo) -> ! {
     Cpu::disable_interrupts();
-    error!("Disabled interrupts");
+    if panic_path(&IN_PANIC) == PanicPath::Minimal {
+        let _ = writeln!(PanicSerial, "Kernel panic while panicking, halting");
+        Cpu::halt();
+    }
 
-    error!("Kernel panic");
+    // the panic may have happened with the serial lock held, so bypass it
+    let _ = writeln!(PanicSerial, "[ Error ] Kernel panic, interrupts disabled");
     if let Some(message) = panic_info.message() {
-        print_raw!("{}\n", message);
+        let _ = writeln!(PanicSerial, "{}", message);
     }
 
     if let Some(location) = panic_info.location() {
-        print_raw!("{}\n", location);
+        let _ = writeln!(PanicSerial, "{}", location);
     }
 
-    Cpu::dump_registers();
+    let _ = write!(PanicSerial, "{}", Registers::capture());
+
+    // drawn last so the log output above doesn't scroll over it
+    paint_panic_screen(panic_info);
+
     Cpu::halt();
 }
+
+/// How much a panic may do before halting
+#[derive(Debug, Eq, PartialEq)]
+pub enum PanicPath {
+    /// Log the message and registers and paint the panic screen
+    Full,
+    /// Write a single line to the serial port, bypassing its lock
+    Minimal,
+}
+
+/// Only the first panic takes the full path, `in_panic` remembers that one happened
+pub fn panic_path(in_panic: &AtomicBool) -> PanicPath {
+    if in_panic.swap(true, Ordering::SeqCst) {
+        PanicPath::Minimal
+    } else {
+        PanicPath::Full
+    }
+}
+
+/// Fills the framebuffer with red and writes the panic message on it, straight into the
+/// framebuffer so a broken log path can't hide it.
+fn paint_panic_screen(panic_info: &PanicInfo) {
+    try_paint_panic_screen(|writer| {
+        let _ = writeln!(writer, "KERNEL PANIC\n");
+        if let Some(message) = panic_info.message() {
+            let _ = writeln!(writer, "{}", message);
+        }
+        if let Some(location) = panic_info.location() {
+            let _ = writeln!(writer, "at {}", location);
+        }
+    });
+}
+
+/// Paints the panic screen and lets `write` put text on it. Returns false without calling
+/// `write` if there's no framebuffer or its lock is taken, e.g. when the panic happened
+/// while drawing.
+pub fn try_paint_panic_screen(write: impl FnOnce(&mut dyn Write)) -> bool {
+    // whoever holds the lock is never going to release it, don't wait for them
+    let mut drawer = match VGA_DRAWER.try_lock() {
+        Some(drawer) => drawer,
+        None => return false,
+    };
+    let framebuffer = match &mut *drawer {
+        PossiblyUninit::Init(drawer) => &mut drawer.buffer,
+        PossiblyUninit::Uninit => return false,
+    };
+
+    for y in 0..framebuffer.height() {
+        for x in 0..framebuffer.width() {
+            framebuffer.write_pixel(BANNER_BACKGROUND, x, y);
+        }
+    }
+
+    let mut writer = PanicScreenWriter {
+        framebuffer,
+        col: 0,
+        row: 0,
+    };
+    write(&mut writer);
+
+    writer.framebuffer.commit();
+    true
+}
+
+/// Draws text onto the panic screen without going through the logging buffers
+struct PanicScreenWriter<'a> {
+    framebuffer: &'a mut VGAFramebuffer,
+    col: usize,
+    row: usize,
+}
+
+impl PanicScreenWriter<'_> {
+    fn cols(&self) -> usize {
+        self.framebuffer.width() / GLYPH_SIZE
+    }
+
+    fn rows(&self) -> usize {
+        self.framebuffer.height() / GLYPH_SIZE
+    }
+
+    fn new_line(&mut self) {
+        self.col = 0;
+        self.row += 1;
+    }
+
+    fn draw_char(&mut self, ch: char) {
+        if self.col >= self.cols() {
+            self.new_line();
+        }
+        if self.row >= self.rows() {
+            return;
+        }
+
+        let glyph = BASIC_FONTS.get(ch).unwrap_or([0; GLYPH_SIZE]);
+        for (y_offset, scanline) in glyph.iter().enumerate() {
+            for bit_idx in 0..GLYPH_SIZE {
+                if scanline >> bit_idx & 1 == 1 {
+                    self.framebuffer.write_pixel(
+                        BANNER_FOREGROUND,
+                        self.col * GLYPH_SIZE + bit_idx,
+                        self.row * GLYPH_SIZE + y_offset,
+                    );
+                }
+            }
+        }
+
+        self.col += 1;
+    }
+}
+
+impl Write for PanicScreenWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for ch in s.chars() {
+            match ch {
+                '\n' => self.new_line(),
+                _ => self.draw_char(ch),
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,393 @@
+use crate::arch::cpu::cpuid;
+use crate::arch::interrupts::pit8254::get_global_ms;
+use crate::arch::mm::paging::PhysicalAddress;
+use crate::arch::mm::PAGE_SIZE;
+use crate::arch::power;
+use crate::fs::cwd::Cwd;
+use crate::fs::fd::FdTable;
+use crate::fs::vfs::VFS;
+use crate::fs::FSNodeType;
+use crate::kutils::errors::ErrorCode;
+use crate::kutils::hexdump::{hexdump, hexdump_bytes};
+use crate::kutils::units::format_duration;
+use crate::task::keyboard::LineReader;
+use crate::MEMORY_CONTROLLER;
+use core::fmt::Write;
+use lazy_static::lazy_static;
+use spin::Mutex;
+
+const PROMPT: &str = "> ";
+/// Most bytes a single `peek` reads
+const PEEK_MAX_LEN: usize = PAGE_SIZE;
+
+lazy_static! {
+    static ref CWD: Mutex<Cwd> = Mutex::new(Cwd::new());
+}
+
+struct Command {
+    name: &'static str,
+    usage: &'static str,
+    run: fn(&mut dyn Write, &[&str]) -> Result<(), ErrorCode>,
+}
+
+static COMMANDS: &[Command] = &[
+    Command {
+        name: "help",
+        usage: "help",
+        run: help,
+    },
+    Command {
+        name: "dump",
+        usage: "dump <addr> <len>",
+        run: dump,
+    },
+    Command {
+        name: "cd",
+        usage: "cd [path]",
+        run: cd,
+    },
+    Command {
+        name: "pwd",
+        usage: "pwd",
+        run: pwd,
+    },
+    Command {
+        name: "cat",
+        usage: "cat <path>",
+        run: cat,
+    },
+    Command {
+        name: "ls",
+        usage: "ls [path]",
+        run: ls,
+    },
+    Command {
+        name: "echo",
+        usage: "echo <text> [> path | >> path]",
+        run: echo,
+    },
+    Command {
+        name: "mem",
+        usage: "mem",
+        run: mem,
+    },
+    Command {
+        name: "uptime",
+        usage: "uptime",
+        run: uptime,
+    },
+    Command {
+        name: "cpuinfo",
+        usage: "cpuinfo",
+        run: cpuinfo,
+    },
+    Command {
+        name: "reboot",
+        usage: "reboot",
+        run: reboot,
+    },
+    Command {
+        name: "shutdown",
+        usage: "shutdown",
+        run: shutdown,
+    },
+    Command {
+        name: "peek",
+        usage: "peek <phys> <len>",
+        run: peek,
+    },
+    Command {
+        name: "poke",
+        usage: "poke <phys> <byte>",
+        run: poke,
+    },
+];
+
+/// Reads commands from the keyboard and runs them, forever
+pub async fn run() {
+    let mut reader = LineReader::new();
+
+    loop {
+        print_raw!("{} {}", CWD.lock().path(), PROMPT);
+        let line = reader.read_line().await;
+        execute(&mut Console, &line);
+    }
+}
+
+/// Writes command output to the screen and serial
+struct Console;
+
+impl Write for Console {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        print_raw!("{}", s);
+        Ok(())
+    }
+}
+
+/// Splits a command line into words on any amount of whitespace
+pub fn split_line(line: &str) -> Vec<&str> {
+    line.split_whitespace().collect()
+}
+
+/// Runs a single command line, writing its output and any error to `out`
+pub fn execute(out: &mut dyn Write, line: &str) {
+    let args = split_line(line);
+    let Some(&name) = args.first() else {
+        return;
+    };
+
+    match COMMANDS.iter().find(|command| command.name == name) {
+        Some(command) => {
+            if let Err(err) = (command.run)(out, &args[1..]) {
+                let _ = writeln!(out, "{}: {:?}", name, err);
+            }
+        }
+        None => {
+            let _ = writeln!(out, "{}: command not found", name);
+        }
+    }
+}
+
+fn help(out: &mut dyn Write, _args: &[&str]) -> Result<(), ErrorCode> {
+    for command in COMMANDS {
+        writeln!(out, "{}", command.usage)?;
+    }
+
+    Ok(())
+}
+
+/// Hexdumps kernel virtual memory to serial. Faults like any other access if it isn't mapped.
+fn dump(_out: &mut dyn Write, args: &[&str]) -> Result<(), ErrorCode> {
+    let [addr, len] = args else {
+        return Err(ErrorCode::EINVAL);
+    };
+    let addr = parse_number(addr)?;
+    let len = parse_number(len)?;
+
+    unsafe {
+        hexdump(addr as *const u8, len);
+    }
+
+    Ok(())
+}
+
+/// Changes the working directory, to the root if no path is given
+fn cd(_out: &mut dyn Write, args: &[&str]) -> Result<(), ErrorCode> {
+    match args {
+        [] => CWD.lock().chdir("/"),
+        [path] => CWD.lock().chdir(path),
+        _ => Err(ErrorCode::EINVAL),
+    }
+}
+
+fn pwd(out: &mut dyn Write, _args: &[&str]) -> Result<(), ErrorCode> {
+    writeln!(out, "{}", CWD.lock().path())?;
+
+    Ok(())
+}
+
+fn cat(out: &mut dyn Write, args: &[&str]) -> Result<(), ErrorCode> {
+    let [path] = args else {
+        return Err(ErrorCode::EINVAL);
+    };
+    let path = CWD.lock().resolve(path);
+
+    let mut fd_table = FdTable::new();
+    let fd = fd_table.open(path)?;
+    let contents = fd_table.read_to_end(fd);
+    fd_table.close(fd)?;
+
+    write!(out, "{}", String::from_utf8_lossy(&contents?))?;
+    Ok(())
+}
+
+/// Lists a directory sorted by name, directories get a trailing `/` and files their size
+fn ls(out: &mut dyn Write, args: &[&str]) -> Result<(), ErrorCode> {
+    let path = match args {
+        [] => CWD.lock().path().clone(),
+        [path] => CWD.lock().resolve(path),
+        _ => return Err(ErrorCode::EINVAL),
+    };
+
+    let mut vfs = VFS.lock();
+    let node = vfs.open(path.clone())?;
+    if node.typ() != FSNodeType::Dir {
+        print_ls_entry(out, node.name(), node.typ(), vfs.fsize(path)?)?;
+        return Ok(());
+    }
+
+    let mut nodes = vfs.list_path(path.clone())?;
+    nodes.sort_by(|a, b| a.name().cmp(b.name()));
+    for node in nodes {
+        // node paths are relative to their mountpoint, build the full path from ours
+        let size = match node.typ() {
+            FSNodeType::File => vfs.fsize(path.append(node.name()))?,
+            _ => 0,
+        };
+        print_ls_entry(out, node.name(), node.typ(), size)?;
+    }
+
+    Ok(())
+}
+
+fn print_ls_entry(
+    out: &mut dyn Write,
+    name: &str,
+    typ: FSNodeType,
+    size: usize,
+) -> Result<(), ErrorCode> {
+    match typ {
+        FSNodeType::Dir => writeln!(out, "{:>8} {}/", "", name)?,
+        _ => writeln!(out, "{:>8} {}", size, name)?,
+    }
+
+    Ok(())
+}
+
+/// Prints `text`, or writes it to a file with `>` and appends it with `>>`.
+/// The file is created if it doesn't exist.
+fn echo(out: &mut dyn Write, args: &[&str]) -> Result<(), ErrorCode> {
+    let Some(redirect) = args.iter().position(|arg| *arg == ">" || *arg == ">>") else {
+        writeln!(out, "{}", args.join(" "))?;
+        return Ok(());
+    };
+    let [path] = &args[redirect + 1..] else {
+        return Err(ErrorCode::EINVAL);
+    };
+    let text = format!("{}\n", args[..redirect].join(" "));
+    let path = CWD.lock().resolve(path);
+
+    // bind it first, the guard would otherwise stay locked through the match
+    let opened = VFS.lock().open(path.clone());
+    let node = match opened {
+        Err(ErrorCode::ENOENT) => VFS.lock().create_file(path.clone())?,
+        node => node?,
+    };
+    if node.typ() == FSNodeType::Dir {
+        return Err(ErrorCode::EISDIR);
+    }
+
+    let mut fd_table = FdTable::new();
+    let fd = fd_table.open(path)?;
+    let written = if args[redirect] == ">>" {
+        fd_table.append(fd, text.as_bytes())
+    } else {
+        fd_table
+            .truncate(fd, 0)
+            .and_then(|()| fd_table.write(fd, text.as_bytes()))
+    };
+    fd_table.close(fd)?;
+
+    written.map(|_| ())
+}
+
+/// Prints physical memory and kernel heap usage
+fn mem(out: &mut dyn Write, _args: &[&str]) -> Result<(), ErrorCode> {
+    let memory_controller = MEMORY_CONTROLLER.try_get().map_err(|_| ErrorCode::ENODEV)?;
+    write!(out, "{}", memory_controller.lock().stats())?;
+
+    Ok(())
+}
+
+/// Prints the time since boot. There's no RTC driver yet, so no wall clock time either.
+fn uptime(out: &mut dyn Write, _args: &[&str]) -> Result<(), ErrorCode> {
+    writeln!(out, "up {}", format_duration(get_global_ms()))?;
+
+    Ok(())
+}
+
+fn cpuinfo(out: &mut dyn Write, _args: &[&str]) -> Result<(), ErrorCode> {
+    writeln!(out, "vendor:   {}", cpuid::vendor())?;
+    if let Some(brand) = cpuid::brand() {
+        writeln!(out, "brand:    {}", brand)?;
+    }
+    writeln!(
+        out,
+        "features: {}",
+        cpuid::features().names().collect::<Vec<_>>().join(" ")
+    )?;
+
+    Ok(())
+}
+
+fn reboot(_out: &mut dyn Write, _args: &[&str]) -> Result<(), ErrorCode> {
+    power::reboot();
+}
+
+fn shutdown(_out: &mut dyn Write, _args: &[&str]) -> Result<(), ErrorCode> {
+    power::shutdown()
+}
+
+/// Hexdumps physical memory to serial. Only RAM from the memory map can be read.
+fn peek(_out: &mut dyn Write, args: &[&str]) -> Result<(), ErrorCode> {
+    let (phys, len) = parse_peek_args(args)?;
+
+    // copy it out first, the memory controller stays locked while frames are mapped
+    let bytes = {
+        let mut memory_controller = MEMORY_CONTROLLER
+            .try_get()
+            .map_err(|_| ErrorCode::ENODEV)?
+            .lock();
+        if !memory_controller.is_in_memory_map(phys, len) {
+            return Err(ErrorCode::EFAULT);
+        }
+
+        let mut bytes = Vec::with_capacity(len);
+        while bytes.len() < len {
+            let addr = phys + bytes.len();
+            let offset = addr % PAGE_SIZE;
+            let chunk = (len - bytes.len()).min(PAGE_SIZE - offset);
+            memory_controller.with_phys_mapped(addr, |frame| {
+                bytes.extend_from_slice(&frame[offset..offset + chunk]);
+            });
+        }
+        bytes
+    };
+    hexdump_bytes(phys, &bytes);
+
+    Ok(())
+}
+
+/// Parses `peek`'s `<phys> <len>`. `len` has to be between 1 and [`PEEK_MAX_LEN`].
+pub fn parse_peek_args(args: &[&str]) -> Result<(PhysicalAddress, usize), ErrorCode> {
+    let [phys, len] = args else {
+        return Err(ErrorCode::EINVAL);
+    };
+    let phys = parse_number(phys)?;
+    let len = parse_number(len)?;
+    if len == 0 || len > PEEK_MAX_LEN {
+        return Err(ErrorCode::EINVAL);
+    }
+
+    Ok((phys, len))
+}
+
+/// Writes a single byte of physical memory. Only RAM from the memory map can be written.
+fn poke(_out: &mut dyn Write, args: &[&str]) -> Result<(), ErrorCode> {
+    let [phys, byte] = args else {
+        return Err(ErrorCode::EINVAL);
+    };
+    let phys = parse_number(phys)?;
+    let byte = u8::try_from(parse_number(byte)?).map_err(|_| ErrorCode::EINVAL)?;
+
+    let mut memory_controller = MEMORY_CONTROLLER
+        .try_get()
+        .map_err(|_| ErrorCode::ENODEV)?
+        .lock();
+    if !memory_controller.is_in_memory_map(phys, 1) {
+        return Err(ErrorCode::EFAULT);
+    }
+
+    memory_controller.with_phys_mapped(phys, |frame| frame[phys % PAGE_SIZE] = byte);
+    Ok(())
+}
+
+/// Parses a decimal or `0x`-prefixed hex number
+pub fn parse_number(s: &str) -> Result<usize, ErrorCode> {
+    let parsed = match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+
+    parsed.map_err(|_| ErrorCode::EINVAL)
+}
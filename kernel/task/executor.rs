@@ -21,15 +21,28 @@ impl Executor {
         }
     }
 
-    pub fn spawn(&mut self, task: Task) {
+    /// Queues `task` to be polled and returns its id.
+    ///
+    /// A `Task` owns its future, so the same future can't be spawned twice. Ids are unique,
+    /// a duplicate would mean the id counter wrapped around.
+    pub fn spawn(&mut self, task: Task) -> TaskId {
         let task_id = task.id;
         if self.tasks.insert(task.id, task).is_some() {
             panic!("task with same ID already in tasks");
         }
         self.task_queue.push(task_id).expect("Task queue full");
+
+        task_id
+    }
+
+    /// Tasks that haven't finished yet
+    #[allow(unused)]
+    pub fn task_count(&self) -> usize {
+        self.tasks.len()
     }
 
-    fn run_ready_tasks(&mut self) {
+    /// Polls every queued task once, tasks woken meanwhile included
+    pub fn run_ready_tasks(&mut self) {
         while let Some(task_id) = self.task_queue.pop() {
             let task = match self.tasks.get_mut(&task_id) {
                 Some(task) => task,
@@ -59,12 +72,18 @@ impl Executor {
         }
     }
 
-    fn sleep_if_idle(&self) {
+    /// Halts until the next interrupt if no task is ready. Returns whether it halted.
+    ///
+    /// The queue is checked with interrupts disabled so a wakeup can't slip in between the
+    /// check and `hlt`. `sti; hlt` then enables interrupts and halts atomically.
+    pub fn sleep_if_idle(&self) -> bool {
         Cpu::disable_interrupts();
         if self.task_queue.is_empty() {
             Cpu::enable_interrupts_and_halt();
+            true
         } else {
-            Cpu::disable_interrupts();
+            Cpu::enable_interrupts();
+            false
         }
     }
 }
@@ -82,8 +101,12 @@ impl TaskWaker {
         }))
     }
 
+    /// Queues the task to be polled again. Runs in interrupt context (e.g. from the keyboard
+    /// handler through `AtomicWaker`), so it must neither block nor panic.
     fn wake_task(&self) {
-        self.task_queue.push(self.task_id).expect("task queue full");
+        if self.task_queue.push(self.task_id).is_err() {
+            warn!("Task queue full; dropping wakeup for {:?}", self.task_id);
+        }
     }
 }
 
@@ -91,4 +114,8 @@ impl Wake for TaskWaker {
     fn wake(self: Arc<Self>) {
         self.wake_task();
     }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wake_task();
+    }
 }
@@ -56,18 +56,48 @@ pub(crate) fn add_scancode(scancode: u8) {
     }
 }
 
-pub async fn print_keypresses() {
-    let mut scancodes = ScancodeStream::new();
-    let mut keyboard = Keyboard::<layouts::Us104Key, ScancodeSet1>::new(HandleControl::Ignore);
+/// Reads lines typed on the keyboard, echoing them to the console
+pub struct LineReader {
+    scancodes: ScancodeStream,
+    keyboard: Keyboard<layouts::Us104Key, ScancodeSet1>,
+}
+
+impl LineReader {
+    pub fn new() -> Self {
+        Self {
+            scancodes: ScancodeStream::new(),
+            keyboard: Keyboard::new(HandleControl::Ignore),
+        }
+    }
 
-    while let Some(scancode) = scancodes.next().await {
-        if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
-            if let Some(key) = keyboard.process_keyevent(key_event) {
-                match key {
-                    DecodedKey::Unicode(character) => print_raw!("{}", character),
-                    DecodedKey::RawKey(key) => print_raw!("{:?}", key),
+    /// Waits for the next line, without the trailing newline
+    pub async fn read_line(&mut self) -> String {
+        let mut line = String::new();
+
+        while let Some(scancode) = self.scancodes.next().await {
+            let key = match self.keyboard.add_byte(scancode) {
+                Ok(Some(key_event)) => self.keyboard.process_keyevent(key_event),
+                _ => None,
+            };
+
+            match key {
+                Some(DecodedKey::Unicode('\n')) => {
+                    print_raw!("\n");
+                    break;
+                }
+                Some(DecodedKey::Unicode('\x08')) => {
+                    if line.pop().is_some() {
+                        serial_print!("\x08 \x08");
+                    }
                 }
+                Some(DecodedKey::Unicode(character)) => {
+                    print_raw!("{}", character);
+                    line.push(character);
+                }
+                Some(DecodedKey::RawKey(_)) | None => {}
             }
         }
+
+        line
     }
 }
@@ -1,3 +1,4 @@
+use crate::arch::mm::paging::translate_addr;
 use crate::kutils::possibly_uninit::PossiblyUninit;
 use multiboot2::{BootInformation, FramebufferType};
 use spin::Mutex;
@@ -40,7 +41,19 @@ pub struct VGAFramebuffer {
 }
 
 impl VGAFramebuffer {
+    /// # SAFETY
+    /// `addr` must point to a framebuffer of `height * pitch` bytes with the given layout.
+    /// Panics if the region isn't mapped.
     pub unsafe fn new(addr: u64, height: usize, width: usize, pitch: usize, bpp: usize) -> Self {
+        let start = addr as usize;
+        let end = start + height * pitch - 1;
+        if let Err(pos) = check_mapped(start, height * pitch, translate_addr) {
+            panic!(
+                "framebuffer at {:#x}-{:#x} is not mapped ({:#x} has no mapping)",
+                start, end, pos
+            );
+        }
+
         Self {
             secondary_buffer: vec![0; height * pitch],
             buffer: addr as *mut u8,
@@ -59,7 +72,7 @@ impl VGAFramebuffer {
         }
     }
 
-    fn clear_y(&mut self, pos_y: usize) {
+    pub fn clear_y(&mut self, pos_y: usize) {
         for pos_x in 0..self.width {
             self.write_pixel(Pixel { r: 0, g: 0, b: 0 }, pos_x, pos_y);
         }
@@ -100,6 +113,38 @@ impl VGAFramebuffer {
         }
     }
 
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Shifts the contents up by `pixels` scanlines and blanks the scanlines exposed at the bottom
+    pub fn move_up(&mut self, pixels: usize) {
+        let pixels = pixels.min(self.height);
+        let shifted = pixels * self.pitch;
+        let size = self.size();
+
+        self.secondary_buffer.copy_within(shifted..size, 0);
+        self.secondary_buffer[size - shifted..].fill(0);
+    }
+
+    /// Size of the framebuffer in bytes
+    pub fn size(&self) -> usize {
+        self.height * self.pitch
+    }
+
+    /// Raw bytes of the secondary buffer, visible after the next `commit`
+    pub fn bytes(&self) -> &[u8] {
+        &self.secondary_buffer
+    }
+
+    pub fn bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.secondary_buffer
+    }
+
     pub fn commit(&mut self) {
         unsafe {
             core::ptr::copy_nonoverlapping(
@@ -111,6 +156,22 @@ impl VGAFramebuffer {
     }
 }
 
+/// Checks that the first and last byte of `len` bytes at `start` have a mapping according
+/// to `translate`. Returns the first address that doesn't.
+pub fn check_mapped(
+    start: usize,
+    len: usize,
+    translate: impl Fn(usize) -> Option<usize>,
+) -> Result<(), usize> {
+    match [start, start + len - 1]
+        .into_iter()
+        .find(|pos| translate(*pos).is_none())
+    {
+        Some(pos) => Err(pos),
+        None => Ok(()),
+    }
+}
+
 /// # SAFETY
 /// It is safe to share vga framebuffer between threads
 unsafe impl Send for VGAFramebuffer {}